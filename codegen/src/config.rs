@@ -0,0 +1,634 @@
+use heck::ToPascalCase as _;
+use proc_macro2::{Delimiter, Group, Spacing, Span, TokenTree};
+use quote::{ToTokens, TokenStreamExt as _};
+use syn::ext::IdentExt as _;
+use syn::parse::{Parse, ParseStream, Parser as _};
+
+use crate::manifest;
+
+/// The arguments of `#[named_future(…)]`
+///
+/// Options that are `None` are taken from the crate-wide defaults, if any.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Args {
+    /// `Send`, or `!Send`: implement [`Send`] for the generated struct
+    pub send: Option<Flag>,
+    /// `Sync`, or `!Sync`: implement [`Sync`] for the generated struct
+    pub sync: Option<Flag>,
+    /// `type = pub Name`: the visibility of the generated struct
+    pub vis: Option<syn::Visibility>,
+    /// `type = pub Name`: the name of the generated struct
+    pub name: Option<syn::Ident>,
+    /// `crate = some::path`: the path to `named-future`
+    pub crate_name: Option<syn::Path>,
+    /// `max_size = 4096`: the size budget of the generated struct in bytes
+    pub max_size: Option<syn::LitInt>,
+    /// `into = Name`: use the user-declared struct `Name` instead of generating one
+    pub into: Option<syn::Ident>,
+    /// `catch_unwind`: catch panics, and return them as `Err(payload)`
+    pub catch_unwind: Option<syn::Ident>,
+    /// `on_cancel = path::to::fn`: call `fn(name)` if the future is dropped before it completed
+    pub on_cancel: Option<syn::Path>,
+    /// `sidecar = Type`: store a `Type` in the struct, and pass it to the `on_cancel` callback
+    pub sidecar: Option<syn::Type>,
+    /// `abortable`: generate `fn {fn}_abortable()` that returns the future and an abort handle
+    pub abortable: Option<syn::Ident>,
+    /// `shared`: generate `type {Type}Shared` and `fn {fn}_shared()` for a cloneable future
+    pub shared: Option<syn::Ident>,
+    /// `Clone`: keep the arguments until the first poll, and implement `Clone` until then
+    pub clone: Option<syn::Ident>,
+    /// `retry`: generate `type {Type}Retry<P>` and `fn {fn}_retry(…, policy)`
+    pub retry: Option<syn::Ident>,
+    /// `timeout`: generate `type {Type}Timeout<S>` and `fn {fn}_with_deadline(…, deadline)`
+    pub timeout: Option<syn::Ident>,
+    /// `builder`: `fn {fn}()` returns a `{Type}Builder` with one setter per argument
+    pub builder: Option<syn::Ident>,
+    /// `blocking`: generate `fn {fn}_blocking()` that runs the future on the current thread
+    pub blocking: Option<syn::Ident>,
+    /// `spawn_blocking` or `spawn_blocking = path::to::EXECUTOR`: run a synchronous `fn` off-thread
+    pub spawn_blocking: Option<SpawnBlocking>,
+    /// `service`: generate `{Type}Service`, a `tower::Service` that calls the function
+    pub service: Option<syn::Ident>,
+    /// `layer`: generate `{Type}Layer`, a `tower::Layer` that wraps a service in `{Type}Service`
+    pub layer: Option<syn::Ident>,
+}
+
+/// The option `spawn_blocking`
+#[derive(Debug, Clone)]
+pub struct SpawnBlocking {
+    /// The name of the option, used for its span
+    pub ident: syn::Ident,
+    /// The `BlockingExecutor`, or `None` for the global `ThreadPool`
+    pub executor: Option<syn::Path>,
+}
+
+/// An option that can be enabled (`Send`), or explicitly disabled (`!Send`)
+#[derive(Debug, Clone)]
+pub struct Flag {
+    /// The name of the option, used for its span
+    pub ident: syn::Ident,
+    /// `false` if the option was negated
+    pub enabled: bool,
+}
+
+impl Flag {
+    /// An enabled option without a specific span
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            ident: syn::Ident::new(name, Span::call_site()),
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Func {
+    pub(crate) attrs: Vec<syn::Attribute>,
+    pub(crate) attrs_split: Option<usize>,
+    pub(crate) vis: syn::Visibility,
+    pub(crate) sig: syn::Signature,
+    pub(crate) body: proc_macro2::TokenTree,
+}
+
+/// All options understood by `#[named_future(…)]`
+const OPTIONS: &[&str] = &[
+    "Send",
+    "Sync",
+    "type",
+    "crate",
+    "max_size",
+    "into",
+    "catch_unwind",
+    "on_cancel",
+    "sidecar",
+    "abortable",
+    "shared",
+    "Clone",
+    "retry",
+    "timeout",
+    "builder",
+    "blocking",
+    "spawn_blocking",
+    "service",
+    "layer",
+];
+
+impl Parse for Args {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let mut result = Args::default();
+        let mut seen = Vec::new();
+        let mut errors = None;
+
+        // Every option is parsed on its own, so that all malformed options are reported at once.
+        let stream: proc_macro2::TokenStream = input.parse()?;
+        let mut stream = stream.into_iter().peekable();
+        while stream.peek().is_some() {
+            let mut option = proc_macro2::TokenStream::new();
+            let mut comma = None;
+            // Angle brackets are not groups, so `sidecar = HashMap<K, V>` must not be split at the
+            // comma between `K` and `V`. The `>` of an arrow `->` does not close a bracket.
+            let mut depth = 0_usize;
+            let mut arrow = false;
+            for tt in stream.by_ref() {
+                match tt {
+                    TokenTree::Punct(punct) if punct.as_char() == ',' && depth == 0 => {
+                        comma = Some(punct);
+                        break;
+                    },
+                    TokenTree::Punct(punct) => {
+                        match punct.as_char() {
+                            '<' => depth += 1,
+                            '>' if !arrow => depth = depth.saturating_sub(1),
+                            _ => {},
+                        }
+                        arrow = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+                        option.extend([TokenTree::Punct(punct)]);
+                    },
+                    tt => {
+                        arrow = false;
+                        option.extend([tt]);
+                    },
+                }
+            }
+
+            let parsed = if option.is_empty() {
+                let span = comma.map_or_else(Span::call_site, |comma| comma.span());
+                Err(syn::Error::new(span, "expected an option before `,`"))
+            } else {
+                let parser = |input: ParseStream<'_>| result.parse_option(input, &mut seen);
+                parser.parse2(option)
+            };
+            if let Err(err) = parsed {
+                combine_errors(&mut errors, err);
+            }
+        }
+        if let (Some(_), Some(into)) = (&result.name, &result.into) {
+            let msg = "`into` and `type` cannot be combined, `into` names a user-declared struct";
+            combine_errors(&mut errors, syn::Error::new(into.span(), msg));
+        }
+        if let Some(sidecar) = &result.sidecar {
+            let msg = if result.on_cancel.is_none() {
+                Some("`sidecar` is passed to the `on_cancel` callback, which is missing")
+            } else if result.into.is_some() {
+                Some("`sidecar` cannot be combined with `into`")
+            } else {
+                None
+            };
+            if let Some(msg) = msg {
+                combine_errors(&mut errors, syn::Error::new_spanned(sidecar, msg));
+            }
+        }
+        if let (Some(abortable), Some(_)) = (&result.abortable, &result.into) {
+            let msg = "`abortable` cannot be combined with `into`";
+            combine_errors(&mut errors, syn::Error::new(abortable.span(), msg));
+        }
+        if let Some(clone) = &result.clone {
+            let msg = if result.into.is_some() {
+                Some("`Clone` cannot be combined with `into`")
+            } else if result.abortable.is_some() {
+                Some("`Clone` cannot be combined with `abortable`")
+            } else {
+                None
+            };
+            if let Some(msg) = msg {
+                combine_errors(&mut errors, syn::Error::new(clone.span(), msg));
+            }
+        }
+        if let (Some(layer), None) = (&result.layer, &result.service) {
+            let msg =
+                "`layer` wraps a service in the `tower::Service` of `service`, which is missing";
+            combine_errors(&mut errors, syn::Error::new(layer.span(), msg));
+        }
+
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(result),
+        }
+    }
+}
+
+impl Args {
+    /// Parse a single option, e.g. `!Send` or `type = pub Name`
+    fn parse_option(
+        &mut self,
+        input: ParseStream<'_>,
+        seen: &mut Vec<&'static str>,
+    ) -> syn::Result<()> {
+        let not: Option<syn::Token![!]> = input.parse()?;
+        let key = input.call(syn::Ident::parse_any)?;
+        let Some(&option) = OPTIONS.iter().find(|option| key == **option) else {
+            return Err(unknown_option(&key));
+        };
+        if seen.contains(&option) {
+            let msg = format!("option `{option}` was already given");
+            return Err(syn::Error::new(key.span(), msg));
+        }
+        seen.push(option);
+
+        match (option, not) {
+            ("Send", not) => {
+                self.send = Some(Flag {
+                    ident: key,
+                    enabled: not.is_none(),
+                });
+            },
+            ("Sync", not) => {
+                self.sync = Some(Flag {
+                    ident: key,
+                    enabled: not.is_none(),
+                });
+            },
+            (_, Some(not)) => {
+                let msg = format!("`!` can only be used with `Send` and `Sync`, not `{option}`");
+                return Err(syn::Error::new_spanned(not, msg));
+            },
+            ("type", None) => {
+                let _: syn::Token![=] = input.parse()?;
+                self.vis = Some(input.parse()?);
+                self.name = Some(input.parse()?);
+            },
+            ("crate", None) => {
+                let _: syn::Token![=] = input.parse()?;
+                self.crate_name = Some(input.parse()?);
+            },
+            ("max_size", None) => {
+                let _: syn::Token![=] = input.parse()?;
+                let max_size: syn::LitInt = input.parse()?;
+                if max_size.base10_parse::<usize>().is_err() {
+                    let msg = "`max_size` must be a non-negative integer, the size in bytes";
+                    return Err(syn::Error::new(max_size.span(), msg));
+                }
+                self.max_size = Some(max_size);
+            },
+            ("into", None) => {
+                let _: syn::Token![=] = input.parse()?;
+                self.into = Some(input.parse()?);
+            },
+            ("catch_unwind", None) => {
+                self.catch_unwind = Some(key);
+            },
+            ("on_cancel", None) => {
+                let _: syn::Token![=] = input.parse()?;
+                self.on_cancel = Some(input.parse()?);
+            },
+            ("sidecar", None) => {
+                let _: syn::Token![=] = input.parse()?;
+                self.sidecar = Some(input.parse()?);
+            },
+            ("abortable", None) => {
+                self.abortable = Some(key);
+            },
+            ("shared", None) => {
+                self.shared = Some(key);
+            },
+            ("Clone", None) => {
+                self.clone = Some(key);
+            },
+            ("retry", None) => {
+                self.retry = Some(key);
+            },
+            ("timeout", None) => {
+                self.timeout = Some(key);
+            },
+            ("builder", None) => {
+                self.builder = Some(key);
+            },
+            ("blocking", None) => {
+                self.blocking = Some(key);
+            },
+            ("spawn_blocking", None) => {
+                let executor = if input.peek(syn::Token![=]) {
+                    let _: syn::Token![=] = input.parse()?;
+                    Some(input.parse()?)
+                } else {
+                    None
+                };
+                self.spawn_blocking = Some(SpawnBlocking {
+                    ident: key,
+                    executor,
+                });
+            },
+            ("service", None) => {
+                self.service = Some(key);
+            },
+            ("layer", None) => {
+                self.layer = Some(key);
+            },
+            _ => unreachable!("unhandled option `{option}`"),
+        }
+        Ok(())
+    }
+}
+
+/// "unknown option `Type`, did you mean `type`?"
+fn unknown_option(key: &syn::Ident) -> syn::Error {
+    let key_str = key.unraw().to_string();
+    let suggestion = OPTIONS
+        .iter()
+        .map(|option| {
+            let distance =
+                edit_distance(&option.to_ascii_lowercase(), &key_str.to_ascii_lowercase());
+            (distance, option)
+        })
+        .filter(|&(distance, _)| distance <= 2 && distance < key_str.len())
+        .min();
+    let msg = match suggestion {
+        Some((_, option)) => format!("unknown option `{key_str}`, did you mean `{option}`?"),
+        None => {
+            let options = OPTIONS
+                .iter()
+                .map(|option| format!("`{option}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("unknown option `{key_str}`, expected one of {options}")
+        },
+    };
+    syn::Error::new(key.span(), msg)
+}
+
+/// Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Add `err` to the `errors` that were collected so far
+pub(crate) fn combine_errors(errors: &mut Option<syn::Error>, err: syn::Error) {
+    match errors {
+        Some(errors) => errors.combine(err),
+        None => *errors = Some(err),
+    }
+}
+
+impl Args {
+    /// Fill in the options that were not given explicitly from the crate-wide defaults
+    pub(crate) fn with_defaults(
+        mut self,
+        defaults: &manifest::Defaults,
+        func: &Func,
+    ) -> syn::Result<Self> {
+        let span = func.sig.ident.span();
+        if self.send.is_none() && defaults.send {
+            self.send = Some(Flag {
+                ident: syn::Ident::new("Send", span),
+                enabled: true,
+            });
+        }
+        if self.sync.is_none() && defaults.sync {
+            self.sync = Some(Flag {
+                ident: syn::Ident::new("Sync", span),
+                enabled: true,
+            });
+        }
+        if self.name.is_none() && self.into.is_none() {
+            if let Some(template) = &defaults.name {
+                let name = template
+                    .replace("{Fn}", &func.sig.ident.unraw().to_string().to_pascal_case())
+                    .replace("{fn}", &func.sig.ident.unraw().to_string());
+                let mut name = syn::parse_str::<syn::Ident>(&name).map_err(|_| {
+                    let msg = format!(
+                        "`package.metadata.named-future.type = {template:?}` does not expand \
+                        to a valid identifier: {name:?}",
+                    );
+                    syn::Error::new(span, msg)
+                })?;
+                name.set_span(span);
+                self.name = Some(name);
+            }
+        }
+        if self.crate_name.is_none() {
+            self.crate_name = default_crate_name(defaults, span)?;
+        }
+        if self.max_size.is_none() {
+            if let Some(max_size) = defaults.max_size {
+                self.max_size = Some(syn::LitInt::new(&max_size.to_string(), span));
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl Parse for Func {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        Ok(Self {
+            attrs_split: attrs_split(&attrs),
+            attrs,
+            vis: input.parse()?,
+            sig: input.parse()?,
+            body: input.parse()?,
+        })
+    }
+}
+
+impl From<syn::ItemFn> for Func {
+    fn from(func: syn::ItemFn) -> Self {
+        let syn::ItemFn {
+            attrs,
+            vis,
+            sig,
+            block,
+        } = func;
+        let mut stmts = proc_macro2::TokenStream::new();
+        stmts.append_all(&block.stmts);
+        let mut body = Group::new(Delimiter::Brace, stmts);
+        body.set_span(block.brace_token.span.join());
+        Self {
+            attrs_split: attrs_split(&attrs),
+            attrs,
+            vis,
+            sig,
+            body: TokenTree::Group(body),
+        }
+    }
+}
+
+/// Find the line `/// # Struct` that separates the docs of the function and the struct
+fn attrs_split(attrs: &[syn::Attribute]) -> Option<usize> {
+    attrs
+        .iter()
+        .enumerate()
+        .find(|(_, attr)| {
+            let Some(str) = get_doc_value(attr) else {
+                return false;
+            };
+            if let Some(doc) = str.value().trim_start().strip_prefix('#') {
+                doc.trim().eq_ignore_ascii_case("struct")
+            } else {
+                false
+            }
+        })
+        .map(|(index, _)| index)
+}
+
+impl Func {
+    /// Reject signatures that cannot be turned into a named future
+    ///
+    /// With `spawn_blocking`, the function must be synchronous instead.
+    pub(crate) fn validate(&self, spawn_blocking: bool) -> syn::Result<()> {
+        let mut errors = None;
+        let mut error = |tokens: &dyn ToTokens, msg: &str| {
+            combine_errors(&mut errors, syn::Error::new_spanned(tokens, msg));
+        };
+
+        let sig = &self.sig;
+        if let Some(constness) = &sig.constness {
+            error(
+                constness,
+                "`#[named_future]` cannot be used on a `const fn`",
+            );
+        }
+        if let Some(unsafety) = &sig.unsafety {
+            error(
+                unsafety,
+                "`#[named_future]` cannot be used on an `unsafe fn`",
+            );
+        }
+        if let Some(abi) = &sig.abi {
+            error(abi, "`#[named_future]` cannot be used on an `extern fn`");
+        }
+        if spawn_blocking {
+            if let Some(asyncness) = &sig.asyncness {
+                error(
+                    asyncness,
+                    "`spawn_blocking` runs a synchronous `fn` on another thread, remove `async`",
+                );
+            }
+            if let Some(param) = sig.generics.lifetimes().next() {
+                error(
+                    param,
+                    "`spawn_blocking` moves the arguments to another thread, \
+                    so they cannot borrow anything",
+                );
+            }
+        } else if sig.asyncness.is_none() {
+            error(
+                &sig.fn_token,
+                "`#[named_future]` can only be used on an `async fn`, add `async` before `fn`",
+            );
+        }
+        for param in &sig.generics.params {
+            match param {
+                syn::GenericParam::Lifetime(_) => {},
+                syn::GenericParam::Type(_) => error(
+                    param,
+                    "`#[named_future]` does not support generic type parameters, \
+                    only lifetime parameters",
+                ),
+                syn::GenericParam::Const(_) => error(
+                    param,
+                    "`#[named_future]` does not support const generic parameters, \
+                    only lifetime parameters",
+                ),
+            }
+        }
+        for input in &sig.inputs {
+            match input {
+                syn::FnArg::Receiver(receiver) => error(
+                    receiver,
+                    "`#[named_future]` does not support methods, \
+                    please use a free function or an associated function without `self`",
+                ),
+                syn::FnArg::Typed(arg) => {
+                    match &*arg.pat {
+                        syn::Pat::Ident(syn::PatIdent {
+                            by_ref: None,
+                            subpat: None,
+                            ..
+                        }) => {},
+                        pat => error(
+                            pat,
+                            "`#[named_future]` only supports plain identifiers as argument \
+                            patterns, please destructure the argument in the function body",
+                        ),
+                    }
+                    if let syn::Type::ImplTrait(ty) = &*arg.ty {
+                        error(
+                            ty,
+                            "`#[named_future]` does not support `impl Trait` arguments, \
+                            please use a concrete type",
+                        );
+                    }
+                },
+            }
+        }
+        if let Some(variadic) = &sig.variadic {
+            error(
+                variadic,
+                "`#[named_future]` does not support variadic functions",
+            );
+        }
+
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) fn get_doc_value(attr: &syn::Attribute) -> Option<&syn::LitStr> {
+    let syn::Meta::NameValue(kv) = &attr.meta else {
+        return None;
+    };
+    let ident = kv.path.get_ident()?;
+    if ident != "doc" {
+        return None;
+    }
+
+    if let syn::Expr::Lit(syn::ExprLit {
+        lit: syn::Lit::Str(lit),
+        ..
+    }) = &kv.value
+    {
+        Some(lit)
+    } else {
+        None
+    }
+}
+
+/// The path to `named-future` as configured in `package.metadata.named-future.crate`
+pub(crate) fn default_crate_name(
+    defaults: &manifest::Defaults,
+    span: proc_macro2::Span,
+) -> syn::Result<Option<syn::Path>> {
+    let Some(path) = &defaults.crate_name else {
+        return Ok(None);
+    };
+    let path = syn::parse_str::<syn::Path>(path).map_err(|_| {
+        let msg = format!("`package.metadata.named-future.crate = {path:?}` is not a valid path");
+        syn::Error::new(span, msg)
+    })?;
+    Ok(Some(respan(path, span)))
+}
+
+/// Give all tokens of `path` the span `span`
+fn respan(path: syn::Path, span: proc_macro2::Span) -> syn::Path {
+    let stream = path
+        .into_token_stream()
+        .into_iter()
+        .map(|mut tt| {
+            tt.set_span(span);
+            tt
+        })
+        .collect();
+    syn::parse2(stream).unwrap()
+}
+
+impl ToTokens for Func {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        self.vis.to_tokens(tokens);
+        self.sig.to_tokens(tokens);
+    }
+}
@@ -0,0 +1,122 @@
+use proc_macro2::TokenStream;
+use quote::quote_spanned;
+use syn::spanned::Spanned as _;
+use syn::{Fields, GenericParam, Ident, parse_quote_spanned};
+
+use crate::config::{combine_errors, default_crate_name};
+use crate::manifest;
+
+/// Expand `#[derive(NamedFuture)] input`, reporting all errors as [`compile_error!`]
+///
+/// This is the implementation of the derive macro `#[derive(NamedFuture)]`.
+#[must_use]
+pub fn expand_derive(input: TokenStream) -> TokenStream {
+    syn::parse2(input)
+        .and_then(derive)
+        .unwrap_or_else(syn::Error::into_compile_error)
+}
+
+/// Implement `DeclaredStruct` for `struct Name(Storage<Self>);`
+///
+/// The struct must be a tuple struct with exactly one field, it may only have lifetime parameters,
+/// and it must not be `#[repr(packed)]`.
+/// The future itself is implemented by `#[named_future(into = Name)]`.
+pub fn derive(item: syn::DeriveInput) -> syn::Result<TokenStream> {
+    let mut errors = None;
+
+    match &item.data {
+        syn::Data::Struct(data) => match &data.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {},
+            fields => combine_errors(
+                &mut errors,
+                syn::Error::new(
+                    fields.span(),
+                    "expected exactly one unnamed field, e.g. `struct Name(Storage<Self>);`",
+                ),
+            ),
+        },
+        syn::Data::Enum(data) => combine_errors(
+            &mut errors,
+            syn::Error::new(data.enum_token.span, "only structs can be named futures"),
+        ),
+        syn::Data::Union(data) => combine_errors(
+            &mut errors,
+            syn::Error::new(data.union_token.span, "only structs can be named futures"),
+        ),
+    }
+    for param in &item.generics.params {
+        match param {
+            GenericParam::Lifetime(_) => {},
+            GenericParam::Type(param) => combine_errors(
+                &mut errors,
+                syn::Error::new(param.ident.span(), "type generics are not supported"),
+            ),
+            GenericParam::Const(param) => combine_errors(
+                &mut errors,
+                syn::Error::new(param.const_token.span, "const generics are not supported"),
+            ),
+        }
+    }
+    for attr in &item.attrs {
+        if !attr.path().is_ident("repr") {
+            continue;
+        }
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("packed") {
+                combine_errors(
+                    &mut errors,
+                    syn::Error::new(
+                        meta.path.span(),
+                        "named futures must not be `#[repr(packed)]`",
+                    ),
+                );
+            }
+            // skip e.g. `align(8)`
+            if meta.input.peek(syn::token::Paren) {
+                let _ = meta.input.parse::<proc_macro2::Group>()?;
+            }
+            Ok(())
+        });
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let span = item.ident.span();
+    let manifest = manifest::Manifest::current();
+    let defaults = manifest
+        .defaults
+        .as_ref()
+        .map_err(|msg| syn::Error::new(span, msg))?;
+    let crate_name = match default_crate_name(defaults, span)? {
+        Some(crate_name) => crate_name,
+        None => {
+            let name = manifest.crate_name.as_deref().unwrap_or("named_future");
+            let name = Ident::new(name, span);
+            parse_quote_spanned!(span => ::#name)
+        },
+    };
+
+    let struct_name = &item.ident;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    Ok(quote_spanned! {
+        span =>
+        unsafe impl #impl_generics #crate_name::machinery::DeclaredStruct
+        for #struct_name #ty_generics #where_clause {
+            #[inline(always)]
+            fn from_storage(storage: #crate_name::Storage<Self>) -> Self {
+                Self(storage)
+            }
+
+            #[inline(always)]
+            fn storage(&mut self) -> &mut #crate_name::Storage<Self> {
+                &mut self.0
+            }
+
+            #[inline(always)]
+            unsafe fn storage_raw(this: *mut Self) -> *mut #crate_name::Storage<Self> {
+                unsafe { ::core::ptr::addr_of_mut!((*this).0) }
+            }
+        }
+    })
+}
@@ -0,0 +1,2093 @@
+// Copyright (c) 2023 René Kijewski <crates.io@k6i.de>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// --- LLVM Exceptions to the Apache 2.0 License ----
+//
+// As an exception, if, as a result of your compiling your source code, portions
+// of this Software are embedded into an Object form of such source code, you
+// may redistribute such embedded portions in such Object form without complying
+// with the conditions of Sections 4(a), 4(b) and 4(d) of the License.
+//
+// In addition, if you combine or link compiled forms of this Software with
+// software that is licensed under the GPLv2 ("Combined Software") and if a
+// court of competent jurisdiction determines that the patent provision (Section
+// 3), the indemnity provision (Section 9) or other Section of the License
+// conflicts with the conditions of the GPLv2, you may retroactively and
+// prospectively choose to deem waived or otherwise exclude such Section(s) of
+// the License, but only in their entirety and only with respect to the Combined
+// Software.
+
+//! # named-future-codegen
+//!
+//! The code generator behind [`#[named_future]`](https://docs.rs/named-future),
+//! usable from other proc macros and `build.rs` generators.
+//!
+//! ```rust
+//! # use quote::quote;
+//! let func: syn::ItemFn = syn::parse_quote! {
+//!     pub async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+//!         factor1 * factor2
+//!     }
+//! };
+//! let mut args = named_future_codegen::Args::default();
+//! args.send = Some(named_future_codegen::Flag::new("Send"));
+//! let tokens = named_future_codegen::expand(args, func).unwrap();
+//! assert!(tokens.to_string().contains("pub struct SlowMul"));
+//! ```
+//!
+//! The path to `named-future` and the crate-wide defaults are read from the `Cargo.toml` of the
+//! crate that is currently being compiled, i.e. the crate that will contain the generated code.
+
+#![allow(unknown_lints)]
+#![warn(absolute_paths_not_starting_with_crate)]
+#![warn(elided_lifetimes_in_paths)]
+#![warn(explicit_outlives_requirements)]
+#![warn(meta_variable_misuse)]
+#![warn(missing_copy_implementations)]
+#![warn(missing_debug_implementations)]
+#![warn(missing_docs)]
+#![warn(non_ascii_idents)]
+#![warn(noop_method_call)]
+#![warn(single_use_lifetimes)]
+#![warn(unused_extern_crates)]
+#![warn(unused_lifetimes)]
+
+mod config;
+mod derive;
+mod manifest;
+
+use heck::ToPascalCase as _;
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{ToTokens as _, format_ident, quote_spanned};
+use syn::ext::IdentExt as _;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned as _;
+use syn::{Ident, parse_quote_spanned};
+
+pub use crate::config::{Args, Flag};
+pub use crate::derive::{derive, expand_derive};
+
+/// Expand `#[named_future(args)] input`, reporting all errors as [`compile_error!`]
+///
+/// This is the implementation of the attribute macro `#[named_future]`.
+#[must_use]
+pub fn expand_attribute(args: TokenStream, input: TokenStream) -> TokenStream {
+    if is_impl(&input) {
+        let item = match syn::parse2::<syn::ItemImpl>(input) {
+            Ok(item) => item,
+            Err(err) => return err.into_compile_error(),
+        };
+        // The `impl` block is emitted in any case, so that its methods do not cause more errors.
+        let expansion = syn::parse2::<Args>(args)
+            .and_then(|args| expand_impl(args, &item))
+            .unwrap_or_else(syn::Error::into_compile_error);
+        return quote::quote!(#item #expansion);
+    }
+
+    let func = match syn::parse2::<config::Func>(input) {
+        Ok(func) => func,
+        Err(err) => return err.into_compile_error(),
+    };
+    let args = syn::parse2::<Args>(args);
+    let spawn_blocking = matches!(&args, Ok(args) if args.spawn_blocking.is_some());
+    let args = match (args, func.validate(spawn_blocking)) {
+        (Ok(args), Ok(())) => args,
+        (Err(mut err), Err(func_err)) => {
+            err.combine(func_err);
+            return err.into_compile_error();
+        },
+        (Err(err), _) | (_, Err(err)) => return err.into_compile_error(),
+    };
+    expand_func(args, func).unwrap_or_else(syn::Error::into_compile_error)
+}
+
+/// Generate a named future for the `async fn` `func`
+///
+/// `args` are the arguments that would be passed to `#[named_future(…)]`.
+/// Options that are not given are taken from the crate-wide defaults.
+pub fn expand(args: Args, func: syn::ItemFn) -> syn::Result<TokenStream> {
+    let func = config::Func::from(func);
+    func.validate(args.spawn_blocking.is_some())?;
+    expand_func(args, func)
+}
+
+/// Generate a named future and a `tower::Service` for the `async fn` with `&self` in `item`
+///
+/// The `impl` block itself is not part of the output. The generated function `fn handle(receiver:
+/// Api, req: Req)` calls the method `Api::handle(&receiver, req)`. It may be unused, because the
+/// method can be called directly. `args` must contain `service`.
+pub fn expand_impl(args: Args, item: &syn::ItemImpl) -> syn::Result<TokenStream> {
+    let impl_span = item.impl_token.span;
+    if args.service.is_none() {
+        let msg = "`#[named_future]` can only be used on an `impl` block with the option `service`";
+        return Err(syn::Error::new(impl_span, msg));
+    }
+    if let Some((_, path, _)) = &item.trait_ {
+        let msg = "`#[named_future(service)]` cannot be used on a trait implementation";
+        return Err(syn::Error::new_spanned(path, msg));
+    }
+    if !item.generics.params.is_empty() {
+        let msg = "`#[named_future(service)]` does not support generic `impl` blocks";
+        return Err(syn::Error::new_spanned(&item.generics, msg));
+    }
+    if let Some(spawn_blocking) = &args.spawn_blocking {
+        let msg = "`spawn_blocking` cannot be used on an `impl` block";
+        return Err(syn::Error::new(spawn_blocking.ident.span(), msg));
+    }
+
+    let methods = item
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syn::ImplItem::Fn(method) => Some(method),
+            _ => None,
+        })
+        .filter(|method| {
+            method.sig.asyncness.is_some()
+                && matches!(
+                    method.sig.inputs.first(),
+                    Some(syn::FnArg::Receiver(syn::Receiver {
+                        reference: Some(_),
+                        mutability: None,
+                        colon_token: None,
+                        ..
+                    })),
+                )
+        })
+        .collect::<Vec<_>>();
+    let method = match methods.as_slice() {
+        [method] => *method,
+        _ => {
+            let msg = format!(
+                "`#[named_future(service)]` on an `impl` block expects exactly one `async fn` \
+                with a `&self` receiver, found {}",
+                methods.len(),
+            );
+            return Err(syn::Error::new(impl_span, msg));
+        },
+    };
+    let sig = &method.sig;
+    let req = match sig.inputs.iter().skip(1).collect::<Vec<_>>().as_slice() {
+        [syn::FnArg::Typed(req)] => req,
+        _ => {
+            let msg = "`#[named_future(service)]` expects the arguments `(&self, request)`";
+            return Err(syn::Error::new(sig.paren_token.span.join(), msg));
+        },
+    };
+
+    // `Self` cannot be used outside of the `impl` block.
+    let self_ty = &*item.self_ty;
+    let req_ty = replace_self(req.ty.to_token_stream(), self_ty);
+    let output = replace_self(sig.output.to_token_stream(), self_ty);
+    let req_ident = match &*req.pat {
+        syn::Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => pat.ident.clone(),
+        pat => Ident::new("req", pat.span()),
+    };
+    let receiver_ident = Ident::new("receiver", sig.inputs[0].span());
+    let docs = method
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"));
+    let vis = &method.vis;
+    let generics = &sig.generics;
+    let ident = &sig.ident;
+
+    let func: syn::ItemFn = parse_quote_spanned! {
+        sig.span() =>
+        #(#docs)*
+        #[allow(dead_code)]
+        #vis async fn #ident #generics(
+            #receiver_ident: #self_ty,
+            #req_ident: #req_ty,
+        ) #output {
+            <#self_ty>::#ident(&#receiver_ident, #req_ident).await
+        }
+    };
+    expand(args, func)
+}
+
+/// Replace the keyword `Self` with `self_ty`
+fn replace_self(tokens: TokenStream, self_ty: &syn::Type) -> TokenStream {
+    let mut result = TokenStream::new();
+    for tt in tokens {
+        match tt {
+            TokenTree::Ident(ident) if ident == "Self" => self_ty.to_tokens(&mut result),
+            TokenTree::Group(group) => {
+                let mut replaced = proc_macro2::Group::new(
+                    group.delimiter(),
+                    replace_self(group.stream(), self_ty),
+                );
+                replaced.set_span(group.span());
+                result.extend([TokenTree::Group(replaced)]);
+            },
+            tt => result.extend([tt]),
+        }
+    }
+    result
+}
+
+/// `true` if `input` is an `impl` block, maybe with attributes
+fn is_impl(input: &TokenStream) -> bool {
+    let parser = |input: syn::parse::ParseStream<'_>| {
+        input.call(syn::Attribute::parse_outer)?;
+        let is_impl = input.peek(syn::Token![impl])
+            || input.peek(syn::Token![unsafe]) && input.peek2(syn::Token![impl]);
+        input.parse::<TokenStream>()?;
+        Ok(is_impl)
+    };
+    syn::parse::Parser::parse2(parser, input.clone()).unwrap_or(false)
+}
+
+fn expand_func(args: Args, mut func: config::Func) -> syn::Result<TokenStream> {
+    let manifest = manifest::Manifest::current();
+    let args = match &manifest.defaults {
+        Ok(defaults) => args.with_defaults(defaults, &func)?,
+        Err(msg) => return Err(syn::Error::new(func.sig.ident.span(), msg)),
+    };
+
+    let defaults = take_defaults(&args, &mut func.sig)?;
+
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+    // Names
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+
+    let function_name = func.sig.ident.clone();
+    let function_name_span = function_name.span();
+
+    let crate_name = args.crate_name.as_ref().cloned().unwrap_or_else(|| {
+        let name = manifest.crate_name.as_deref().unwrap_or("named_future");
+        let name = Ident::new(name, function_name_span);
+        parse_quote_spanned!(function_name_span => ::#name)
+    });
+
+    if let Some(spawn_blocking) = &args.spawn_blocking {
+        spawn_blocking_body(spawn_blocking, &crate_name, &mut func);
+    }
+    let body = &func.body;
+
+    let struct_name = if let Some(name) = args.into.as_ref().or(args.name.as_ref()) {
+        name.clone()
+    } else {
+        Ident::new(
+            &function_name.to_string().to_pascal_case(),
+            function_name_span,
+        )
+    };
+    let struct_name_string = struct_name.to_string();
+
+    // Internal identifiers use mixed-site hygiene, so they cannot clash with the arguments of the
+    // function, or with identifiers of a `macro_rules!` macro that invoked `#[named_future]`.
+    let mixed_site = Span::mixed_site();
+    let gen_ident = function_name.clone();
+    let impl_ident = format_ident!("__{}_implementation", function_name, span = mixed_site);
+    let args_ident = Ident::new("args", mixed_site);
+    let fut_ident = Ident::new("fut", mixed_site);
+    let cx_ident = Ident::new("cx", mixed_site);
+    let slot_ident = Ident::new("slot", mixed_site);
+    let this_ident = Ident::new("this", mixed_site);
+    let f_ident = Ident::new("f", mixed_site);
+
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+    // Attributes
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+
+    let (func_attrs, struct_attrs) = match func.attrs_split {
+        Some(index) => {
+            let (func_attrs, struct_attrs) = func.attrs.split_at(index);
+            (func_attrs, &struct_attrs[1..])
+        },
+        None => (func.attrs.as_slice(), &[][..]),
+    };
+
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+    // Types
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+
+    let arg_types_as_tuple = arg_types_as_tuple(&func)?;
+    let args_pats_as_tuple = args_pats_as_tuple(&func)?;
+    let arg_exprs_with_commas = arg_exprs_with_commas(&func)?;
+    let args_exprs_as_tuple = syn::ExprTuple {
+        attrs: vec![],
+        paren_token: func.sig.paren_token,
+        elems: arg_exprs_with_commas.clone(),
+    };
+
+    let ty_self = ty_self(&func, &struct_name);
+
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+    // Signatures
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+
+    let func_vis = &func.vis;
+    let func_gen = &func.sig.generics;
+    let func_output = match &func.sig.output {
+        syn::ReturnType::Default => parse_quote_spanned!(function_name_span => ()),
+        syn::ReturnType::Type(_, ty) => syn::Type::clone(ty),
+    };
+    let (impl_generics, ty_generics, where_clause) = func_gen.split_for_impl();
+    let phantom = phantom(
+        func_gen,
+        &arg_types_as_tuple,
+        &func_output,
+        function_name_span,
+    );
+    let struct_vis = args.vis.as_ref().unwrap_or(func_vis);
+
+    // With `catch_unwind`, a panic is returned as `Err(payload)`.
+    let (poll_output, poll_fn, poll_opaque_fn) = if let Some(catch_unwind) = &args.catch_unwind {
+        let span = catch_unwind.span();
+        let poll_output = quote_spanned! {
+            span => ::core::result::Result<#func_output, #crate_name::PanicPayload>
+        };
+        (
+            poll_output,
+            Ident::new("poll_catch_unwind", span),
+            Ident::new("poll_opaque_catch_unwind", span),
+        )
+    } else {
+        (
+            func_output.to_token_stream(),
+            Ident::new("poll", function_name_span),
+            Ident::new("poll_opaque", function_name_span),
+        )
+    };
+    // With `abortable`, an aborted future returns `Err(Aborted)`.
+    let poll_output = match &args.abortable {
+        Some(abortable) => quote_spanned! {
+            abortable.span() =>
+            ::core::result::Result<#poll_output, #crate_name::Aborted>
+        },
+        None => poll_output,
+    };
+
+    let mut func_sig = func.sig.clone();
+    func_sig.asyncness = None;
+    func_sig.output = parse_quote_spanned! {
+        function_name_span => -> #struct_name #ty_generics
+    };
+
+    let mut gen_sig = gen_sig(&func, &gen_ident, &args_pats_as_tuple, &arg_types_as_tuple);
+    let impl_send = impl_send(&args, &struct_name, func_gen, &gen_ident, &crate_name);
+    let impl_sync = impl_sync(&args, &struct_name, func_gen, &gen_ident, &crate_name);
+    let assert_max_size = assert_max_size(&args, &struct_name, &ty_self, &crate_name);
+
+    func.sig.ident = impl_ident.clone();
+    for arg in &mut func_sig.inputs {
+        if let syn::FnArg::Typed(arg) = arg {
+            if let syn::Pat::Ident(arg) = &mut *arg.pat {
+                arg.mutability = None;
+            }
+        }
+    }
+
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+    // Implementation
+    // ////////////////////////////////////////////////////////////////////////////////////////////
+
+    let struct_name_span = struct_name.span();
+    let Sidecar {
+        field: sidecar_field,
+        init: sidecar_init,
+        init_raw: sidecar_init_raw,
+        accessors: sidecar_accessors,
+    } = sidecar(&args, &struct_name, func_gen, struct_vis, &slot_ident);
+    let Abortable {
+        field: abort_field,
+        init: abort_init,
+        init_raw: abort_init_raw,
+        items: abort_items,
+    } = abortable(
+        &args,
+        &crate_name,
+        &struct_name,
+        struct_vis,
+        func_vis,
+        &func_sig,
+        &args_exprs_as_tuple,
+        &slot_ident,
+    );
+    let slot_expr = if args.into.is_some() {
+        quote_spanned! {
+            function_name_span =>
+            #crate_name::machinery::slot(
+                <Self as #crate_name::machinery::DeclaredStruct>::storage(self),
+            )
+        }
+    } else {
+        quote_spanned!(function_name_span => &mut self._future)
+    };
+    let Cloneable {
+        field: clone_field,
+        init: clone_init,
+        init_raw: clone_init_raw,
+        items: clone_items,
+    } = cloneable(
+        &args,
+        &crate_name,
+        &struct_name,
+        func_gen,
+        struct_vis,
+        &arg_types_as_tuple,
+        &slot_ident,
+        &args_ident,
+    );
+    let on_cancel = on_cancel(&args, &crate_name, &struct_name_string, &slot_expr);
+    let retry_items = retry(
+        &args,
+        &crate_name,
+        &struct_name,
+        struct_vis,
+        func_vis,
+        &func_sig,
+        &args_exprs_as_tuple,
+    );
+    let timeout_items = timeout(
+        &args,
+        &crate_name,
+        &struct_name,
+        struct_vis,
+        func_vis,
+        &func_sig,
+        &args_exprs_as_tuple,
+    );
+    let blocking_items = blocking(
+        &args,
+        &crate_name,
+        &struct_name,
+        func_vis,
+        &func_sig,
+        &poll_output,
+        &args_exprs_as_tuple,
+    );
+    let service_items = service(
+        &args,
+        &crate_name,
+        &struct_name,
+        struct_vis,
+        &func_sig,
+        &poll_output,
+    )?;
+    let shared_items = shared(
+        &args,
+        &crate_name,
+        &struct_name,
+        struct_vis,
+        func_vis,
+        &func_sig,
+        &args_exprs_as_tuple,
+    );
+    let Backend {
+        opaque_definition,
+        storage,
+        impl_declared,
+        gen_body,
+        new_body,
+        init_body,
+        impl_drop,
+        poll_body,
+    } = if let Some(into) = &args.into {
+        // The struct was declared by the user as `struct Name(Storage<Self>);`.
+        let into_span = into.span();
+        Backend {
+            opaque_definition: None,
+            storage: TokenStream::new(),
+            impl_declared: Some(quote_spanned! {
+                into_span =>
+                unsafe impl #impl_generics #crate_name::machinery::Declared
+                for #struct_name #ty_generics #where_clause {
+                    type Bytes = #crate_name::machinery::Bytes<
+                        { <#ty_self as #crate_name::machinery::NamedFuture>::SIZE_OF },
+                        { <#ty_self as #crate_name::machinery::NamedFuture>::ALIGN_OF },
+                    >;
+                }
+            }),
+            gen_body: quote_spanned! {
+                function_name_span =>
+                #gen_sig {
+                    #impl_ident(#arg_exprs_with_commas).await
+                }
+            },
+            new_body: quote_spanned! {
+                function_name_span =>
+                let #fut_ident = unsafe {
+                    #crate_name::machinery::new(#struct_name_string, &#gen_ident, #args_ident)
+                };
+                <Self as #crate_name::machinery::DeclaredStruct>::from_storage(
+                    #crate_name::machinery::storage(#fut_ident),
+                )
+            },
+            init_body: quote_spanned! {
+                function_name_span =>
+                let #slot_ident =
+                    <Self as #crate_name::machinery::DeclaredStruct>::storage_raw(#slot_ident);
+                let #slot_ident = #crate_name::machinery::slot_raw(#slot_ident);
+                #crate_name::machinery::init(
+                    #struct_name_string,
+                    &#gen_ident,
+                    #slot_ident,
+                    #args_ident,
+                );
+            },
+            impl_drop: Some(quote_spanned! {
+                function_name_span =>
+                impl #impl_generics ::core::ops::Drop
+                for #struct_name #ty_generics #where_clause {
+                    #[inline]
+                    fn drop(&mut self) {
+                        #on_cancel
+                        let #fut_ident =
+                            <Self as #crate_name::machinery::DeclaredStruct>::storage(self);
+                        let #fut_ident = #crate_name::machinery::slot(#fut_ident);
+                        unsafe { #crate_name::machinery::drop(&#gen_ident, #fut_ident) };
+                    }
+                }
+            }),
+            poll_body: quote_spanned! {
+                function_name_span =>
+                let #fut_ident = unsafe {
+                    self.map_unchecked_mut(|this| {
+                        #crate_name::machinery::slot(
+                            <Self as #crate_name::machinery::DeclaredStruct>::storage(this),
+                        )
+                    })
+                };
+                unsafe {
+                    #crate_name::machinery::#poll_fn(
+                        #struct_name_string,
+                        &#gen_ident,
+                        #fut_ident,
+                        #cx_ident,
+                    )
+                }
+            },
+        }
+    } else if cfg!(feature = "nightly") {
+        // The unnamed future is stored as a `type_alias_impl_trait`, so no `unsafe` tricks are needed.
+        let opaque_ident = format_ident!("__{}Opaque", struct_name, span = mixed_site);
+        gen_sig.asyncness = None;
+        gen_sig.output = parse_quote_spanned! {
+            function_name_span => -> #opaque_ident #ty_generics
+        };
+        // With `Clone`, the future is created when it is polled for the first time.
+        let (new_slot, start) = if args.clone.is_some() {
+            (
+                quote_spanned! {
+                    function_name_span =>
+                    #crate_name::machinery::Slot::not_started(#struct_name_string)
+                },
+                Some(quote_spanned! {
+                    function_name_span =>
+                    if let ::core::option::Option::Some(#args_ident) = #this_ident._args.take() {
+                        #crate_name::machinery::start_opaque(
+                            &mut #this_ident._future,
+                            #gen_ident(#args_ident),
+                        );
+                    }
+                }),
+            )
+        } else {
+            (
+                quote_spanned! {
+                    function_name_span =>
+                    #crate_name::machinery::Slot::new(#struct_name_string, #gen_ident(#args_ident))
+                },
+                None,
+            )
+        };
+        Backend {
+            opaque_definition: Some(quote_spanned! {
+                struct_name_span =>
+                #[doc(hidden)]
+                type #opaque_ident #func_gen #where_clause =
+                    impl ::core::future::Future<Output = #func_output>;
+            }),
+            storage: quote_spanned! {
+                struct_name_span =>
+                #crate_name::machinery::Slot<#opaque_ident #ty_generics>
+            },
+            impl_declared: None,
+            gen_body: quote_spanned! {
+                function_name_span =>
+                #[define_opaque(#opaque_ident)]
+                #gen_sig {
+                    #impl_ident(#arg_exprs_with_commas)
+                }
+            },
+            new_body: quote_spanned! {
+                function_name_span =>
+                Self {
+                    _future: #new_slot,
+                    _not_send_or_sync: ::core::marker::PhantomData,
+                    _pin: ::core::marker::PhantomPinned,
+                    _phantom: ::core::marker::PhantomData,
+                    #sidecar_init
+                    #abort_init
+                    #clone_init
+                }
+            },
+            init_body: quote_spanned! {
+                function_name_span =>
+                // The optional fields for the sidecar, `abortable` and `Clone` are written below.
+                // All other fields are zero-sized.
+                ::core::ptr::addr_of_mut!((*#slot_ident)._future).write(#new_slot);
+                #sidecar_init_raw
+                #abort_init_raw
+                #clone_init_raw
+            },
+            // The future is dropped by `Slot`.
+            impl_drop: on_cancel.as_ref().map(|on_cancel| {
+                quote_spanned! {
+                    function_name_span =>
+                    impl #impl_generics ::core::ops::Drop
+                    for #struct_name #ty_generics #where_clause {
+                        #[inline]
+                        fn drop(&mut self) {
+                            #on_cancel
+                        }
+                    }
+                }
+            }),
+            poll_body: if args.abortable.is_some() {
+                quote_spanned! {
+                    function_name_span =>
+                    let #this_ident = unsafe { self.get_unchecked_mut() };
+                    let #fut_ident = unsafe { ::core::pin::Pin::new_unchecked(&mut #this_ident._future) };
+                    #crate_name::machinery::poll_opaque_abortable(
+                        &#this_ident._abort,
+                        #fut_ident,
+                        #cx_ident,
+                        |#fut_ident, #cx_ident| {
+                            #crate_name::machinery::#poll_opaque_fn(
+                                #struct_name_string,
+                                #fut_ident,
+                                #cx_ident,
+                            )
+                        },
+                    )
+                }
+            } else {
+                quote_spanned! {
+                    function_name_span =>
+                    let #fut_ident = unsafe {
+                        self.map_unchecked_mut(|#this_ident| {
+                            #start
+                            &mut #this_ident._future
+                        })
+                    };
+                    #crate_name::machinery::#poll_opaque_fn(#struct_name_string, #fut_ident, #cx_ident)
+                }
+            },
+        }
+    } else {
+        // With `Clone`, the future is created when it is polled for the first time.
+        let (new_slot, init_slot, start) = if args.clone.is_some() {
+            let new_slot = quote_spanned! {
+                function_name_span =>
+                #crate_name::machinery::Slot::not_started(#struct_name_string)
+            };
+            (
+                new_slot.clone(),
+                quote_spanned! {
+                    function_name_span =>
+                    ::core::ptr::addr_of_mut!((*#slot_ident)._future).write(#new_slot);
+                },
+                Some(quote_spanned! {
+                    function_name_span =>
+                    if let ::core::option::Option::Some(#args_ident) = #this_ident._args.take() {
+                        #crate_name::machinery::start(
+                            #struct_name_string,
+                            &#gen_ident,
+                            &mut #this_ident._future,
+                            #args_ident,
+                        );
+                    }
+                }),
+            )
+        } else {
+            (
+                quote_spanned! {
+                    function_name_span =>
+                    unsafe {
+                        #crate_name::machinery::new(#struct_name_string, &#gen_ident, #args_ident)
+                    }
+                },
+                quote_spanned! {
+                    function_name_span =>
+                    #crate_name::machinery::init(
+                        #struct_name_string,
+                        &#gen_ident,
+                        ::core::ptr::addr_of_mut!((*#slot_ident)._future),
+                        #args_ident,
+                    );
+                },
+                None,
+            )
+        };
+        Backend {
+            opaque_definition: None,
+            storage: quote_spanned! {
+                struct_name_span =>
+                #crate_name::machinery::Slot<#crate_name::machinery::Bytes<
+                    { <#ty_self as #crate_name::machinery::NamedFuture>::SIZE_OF },
+                    { <#ty_self as #crate_name::machinery::NamedFuture>::ALIGN_OF },
+                >>
+            },
+            impl_declared: None,
+            gen_body: quote_spanned! {
+                function_name_span =>
+                #gen_sig {
+                    #impl_ident(#arg_exprs_with_commas).await
+                }
+            },
+            new_body: quote_spanned! {
+                function_name_span =>
+                Self {
+                    _future: #new_slot,
+                    _not_send_or_sync: ::core::marker::PhantomData,
+                    _pin: ::core::marker::PhantomPinned,
+                    _phantom: ::core::marker::PhantomData,
+                    #sidecar_init
+                    #abort_init
+                    #clone_init
+                }
+            },
+            init_body: quote_spanned! {
+                function_name_span =>
+                // The optional fields for the sidecar, `abortable` and `Clone` are written below.
+                // All other fields are zero-sized.
+                #init_slot
+                #sidecar_init_raw
+                #abort_init_raw
+                #clone_init_raw
+            },
+            impl_drop: Some(quote_spanned! {
+                function_name_span =>
+                impl #impl_generics ::core::ops::Drop
+                for #struct_name #ty_generics #where_clause {
+                    #[inline]
+                    fn drop(&mut self) {
+                        #on_cancel
+                        unsafe { #crate_name::machinery::drop(&#gen_ident, &mut self._future) };
+                    }
+                }
+            }),
+            poll_body: if args.abortable.is_some() {
+                quote_spanned! {
+                    function_name_span =>
+                    let #this_ident = unsafe { self.get_unchecked_mut() };
+                    let #fut_ident = unsafe { ::core::pin::Pin::new_unchecked(&mut #this_ident._future) };
+                    unsafe {
+                        #crate_name::machinery::poll_abortable(
+                            &#gen_ident,
+                            &#this_ident._abort,
+                            #fut_ident,
+                            #cx_ident,
+                            |#fut_ident, #cx_ident| {
+                                #crate_name::machinery::#poll_fn(
+                                    #struct_name_string,
+                                    &#gen_ident,
+                                    #fut_ident,
+                                    #cx_ident,
+                                )
+                            },
+                        )
+                    }
+                }
+            } else {
+                quote_spanned! {
+                    function_name_span =>
+                    let #fut_ident = unsafe {
+                        self.map_unchecked_mut(|#this_ident| {
+                            #start
+                            &mut #this_ident._future
+                        })
+                    };
+                    unsafe {
+                        #crate_name::machinery::#poll_fn(
+                            #struct_name_string,
+                            &#gen_ident,
+                            #fut_ident,
+                            #cx_ident,
+                        )
+                    }
+                }
+            },
+        }
+    };
+
+    let repr = if sidecar_field.is_some() || abort_field.is_some() || clone_field.is_some() {
+        quote_spanned!(struct_name_span => #[repr(C)])
+    } else {
+        quote_spanned!(struct_name_span => #[repr(transparent)])
+    };
+    let struct_definition = args.into.is_none().then(|| {
+        quote_spanned! {
+            struct_name_span =>
+
+            #(#struct_attrs)*
+            #repr
+            #[must_use = "futures do nothing unless you `.await` or poll them"]
+            #[allow(clippy::type_complexity)]
+            #struct_vis struct #struct_name #func_gen #where_clause {
+                _future: #storage,
+                _not_send_or_sync: ::core::marker::PhantomData<*mut ()>,
+                _pin: ::core::marker::PhantomPinned,
+                _phantom: #phantom,
+                #sidecar_field
+                #abort_field
+                #clone_field
+            }
+
+            #sidecar_accessors
+
+            #opaque_definition
+        }
+    });
+    let impl_debug = args.into.is_none().then(|| {
+        quote_spanned! {
+            function_name_span =>
+            impl #impl_generics ::core::fmt::Debug
+            for #struct_name #ty_generics #where_clause {
+                fn fmt(
+                    &self,
+                    #f_ident: &mut ::core::fmt::Formatter<'_>,
+                ) -> ::core::fmt::Result {
+                    #f_ident.debug_struct(#struct_name_string).finish_non_exhaustive()
+                }
+            }
+        }
+    });
+
+    let func_item = match builder(
+        &args,
+        &crate_name,
+        &struct_name,
+        struct_vis,
+        func_vis,
+        &func_sig,
+        &defaults,
+    ) {
+        Some(builder) => builder,
+        None => quote_spanned! {
+            function_name_span =>
+            #[inline]
+            #[allow(clippy::needless_lifetimes)]
+            #func_vis #func_sig {
+                <#struct_name #ty_generics as #crate_name::machinery::NamedFuture>::
+                    new(#args_exprs_as_tuple)
+            }
+        },
+    };
+
+    Ok(quote_spanned! {
+        function_name_span =>
+
+        #(#func_attrs)*
+        #func_item
+
+        #abort_items
+        #shared_items
+        #retry_items
+        #timeout_items
+        #blocking_items
+        #service_items
+
+        #struct_definition
+
+        #[allow(clippy::needless_lifetimes)]
+        const _: () = {
+            #[inline(always)]
+            #func #body
+
+            const _: () = {
+                #gen_body
+
+                impl #impl_generics #crate_name::machinery::NamedFuture
+                for #struct_name #ty_generics #where_clause {
+                    const NAME: &'static ::core::primitive::str = #struct_name_string;
+                    const ALIGN_OF: ::core::primitive::usize =
+                        #crate_name::machinery::align_of(&#gen_ident);
+                    const SIZE_OF: ::core::primitive::usize =
+                        #crate_name::machinery::size_of(&#gen_ident);
+
+                    type Args = #arg_types_as_tuple;
+
+                    #[inline(always)]
+                    fn new(#args_ident: Self::Args) -> Self {
+                        #new_body
+                    }
+
+                    #[inline(always)]
+                    unsafe fn init_raw(#slot_ident: *mut Self, #args_ident: Self::Args) {
+                        unsafe { #init_body }
+                    }
+                }
+
+                #impl_drop
+
+                impl #impl_generics ::core::future::Future
+                for #struct_name #ty_generics #where_clause {
+                    type Output = #poll_output;
+
+                    #[inline]
+                    fn poll(
+                        self: ::core::pin::Pin<&mut Self>,
+                        #cx_ident: &mut ::core::task::Context<'_>,
+                    ) -> ::core::task::Poll<Self::Output> {
+                        #poll_body
+                    }
+                }
+
+                #impl_debug
+                #clone_items
+                #impl_declared
+                #impl_send
+                #impl_sync
+                #assert_max_size
+            };
+        };
+    })
+}
+
+/// The parts of the expansion for `sidecar = Type`
+#[derive(Default)]
+struct Sidecar {
+    /// Field in the struct definition
+    field: Option<TokenStream>,
+    /// Field in the struct literal of `NamedFuture::new()`
+    init: Option<TokenStream>,
+    /// Statement in `NamedFuture::init_raw()`
+    init_raw: Option<TokenStream>,
+    /// Inherent methods to access the sidecar
+    accessors: Option<TokenStream>,
+}
+
+fn sidecar(
+    args: &Args,
+    struct_name: &Ident,
+    func_gen: &syn::Generics,
+    struct_vis: &syn::Visibility,
+    slot_ident: &Ident,
+) -> Sidecar {
+    let Some(ty) = &args.sidecar else {
+        return Sidecar::default();
+    };
+    let span = ty.span();
+    let (impl_generics, ty_generics, where_clause) = func_gen.split_for_impl();
+    Sidecar {
+        field: Some(quote_spanned!(span => _sidecar: #ty,)),
+        init: Some(quote_spanned!(span => _sidecar: ::core::default::Default::default(),)),
+        init_raw: Some(quote_spanned! {
+            span =>
+            ::core::ptr::addr_of_mut!((*#slot_ident)._sidecar)
+                .write(::core::default::Default::default());
+        }),
+        accessors: Some(quote_spanned! {
+            span =>
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                /// The data that is passed to the `on_cancel` callback
+                #[inline]
+                #struct_vis fn sidecar(&self) -> &#ty {
+                    &self._sidecar
+                }
+
+                /// Mutable access to the data that is passed to the `on_cancel` callback
+                #[inline]
+                #struct_vis fn sidecar_mut(self: ::core::pin::Pin<&mut Self>) -> &mut #ty {
+                    // SAFETY: the sidecar is not structurally pinned
+                    unsafe { &mut self.get_unchecked_mut()._sidecar }
+                }
+            }
+        }),
+    }
+}
+
+/// The parts of the expansion for `abortable`
+#[derive(Default)]
+struct Abortable {
+    /// Field in the struct definition
+    field: Option<TokenStream>,
+    /// Field in the struct literal of `NamedFuture::new()`
+    init: Option<TokenStream>,
+    /// Statement in `NamedFuture::init_raw()`
+    init_raw: Option<TokenStream>,
+    /// The abort handle, and the function `{fn}_abortable()`
+    items: Option<TokenStream>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn abortable(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    struct_vis: &syn::Visibility,
+    func_vis: &syn::Visibility,
+    func_sig: &syn::Signature,
+    args_exprs_as_tuple: &syn::ExprTuple,
+    slot_ident: &Ident,
+) -> Abortable {
+    let Some(abortable) = &args.abortable else {
+        return Abortable::default();
+    };
+    let span = abortable.span();
+    let mixed_site = Span::mixed_site();
+    let registration_ident = Ident::new("registration", mixed_site);
+    let handle_ident = Ident::new("handle", mixed_site);
+    let fut_ident = Ident::new("fut", mixed_site);
+    let state_ident = Ident::new("state", mixed_site);
+    let handle_name = format_ident!("{}AbortHandle", struct_name);
+    let (_, ty_generics, _) = func_sig.generics.split_for_impl();
+
+    let mut abortable_sig = func_sig.clone();
+    abortable_sig.ident = format_ident!("{}_abortable", func_sig.ident);
+    abortable_sig.output = parse_quote_spanned! {
+        span => -> (#struct_name #ty_generics, #handle_name)
+    };
+    let mut abortable_with_sig = abortable_sig.clone();
+    abortable_with_sig.ident = format_ident!("{}_abortable_with", func_sig.ident);
+    abortable_with_sig.inputs.push(parse_quote_spanned! {
+        span => #state_ident: &'static #crate_name::AbortState
+    });
+    let handle_doc = format!(" Aborts a [`{struct_name}`]");
+    let func_doc = format!(
+        " Like [`{}()`], but also return a handle to abort the future",
+        func_sig.ident.unraw(),
+    );
+    let func_with_doc = format!(
+        " Like [`{}()`], but also return a handle to abort the future that uses `state`",
+        func_sig.ident.unraw(),
+    );
+    let func_body = |registration: TokenStream| {
+        quote_spanned! {
+            span =>
+            let (#registration_ident, #handle_ident) = #registration;
+            let mut #fut_ident =
+                <#struct_name #ty_generics as #crate_name::machinery::NamedFuture>::
+                    new(#args_exprs_as_tuple);
+            #fut_ident._abort = #registration_ident;
+            (#fut_ident, #handle_name(#handle_ident))
+        }
+    };
+    let alloc_body = func_body(quote_spanned!(span => #crate_name::machinery::abortable()));
+    let static_body = func_body(quote_spanned! {
+        span => #crate_name::machinery::abortable_with(#state_ident)
+    });
+
+    Abortable {
+        field: Some(quote_spanned!(span => _abort: #crate_name::machinery::AbortRegistration,)),
+        init: Some(quote_spanned!(span => _abort: ::core::default::Default::default(),)),
+        init_raw: Some(quote_spanned! {
+            span =>
+            ::core::ptr::addr_of_mut!((*#slot_ident)._abort)
+                .write(::core::default::Default::default());
+        }),
+        items: Some(quote_spanned! {
+            span =>
+            #[doc = #handle_doc]
+            #[derive(Debug, Clone)]
+            #struct_vis struct #handle_name(#crate_name::machinery::AbortHandle);
+
+            #[allow(dead_code)]
+            impl #handle_name {
+                /// Abort the future, so that it returns `Err(Aborted)` when it is polled next
+                ///
+                /// The task that polls the future is woken up.
+                #[inline]
+                #struct_vis fn abort(&self) {
+                    self.0.abort();
+                }
+
+                /// `true` if [`abort()`](Self::abort) was called
+                #[inline]
+                #[must_use]
+                #struct_vis fn is_aborted(&self) -> bool {
+                    self.0.is_aborted()
+                }
+            }
+
+            // Without `alloc`, only the variant with a `&'static AbortState` is available.
+            // Either variant may be unused.
+            #crate_name::machinery::if_alloc! {
+                #[doc = #func_doc]
+                #[inline]
+                #[allow(dead_code, clippy::needless_lifetimes)]
+                #func_vis #abortable_sig {
+                    #alloc_body
+                }
+            }
+
+            #[doc = #func_with_doc]
+            #[inline]
+            #[allow(dead_code, clippy::needless_lifetimes)]
+            #func_vis #abortable_with_sig {
+                #static_body
+            }
+        }),
+    }
+}
+
+/// The parts of the expansion for `Clone`
+#[derive(Default)]
+struct Cloneable {
+    /// Field in the struct definition
+    field: Option<TokenStream>,
+    /// Field in the struct literal of `NamedFuture::new()`
+    init: Option<TokenStream>,
+    /// Statement in `NamedFuture::init_raw()`
+    init_raw: Option<TokenStream>,
+    /// `impl Clone`, and `try_clone()`
+    items: Option<TokenStream>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cloneable(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    func_gen: &syn::Generics,
+    struct_vis: &syn::Visibility,
+    arg_types_as_tuple: &syn::Type,
+    slot_ident: &Ident,
+    args_ident: &Ident,
+) -> Cloneable {
+    let Some(clone) = &args.clone else {
+        return Cloneable::default();
+    };
+    let span = clone.span();
+    let fut_ident = Ident::new("fut", Span::mixed_site());
+    let (impl_generics, ty_generics, where_clause) = func_gen.split_for_impl();
+    let msg = format!("`{struct_name}` was cloned after it was polled");
+    Cloneable {
+        field: Some(quote_spanned! {
+            span => _args: ::core::option::Option<#arg_types_as_tuple>,
+        }),
+        init: Some(quote_spanned! {
+            span => _args: ::core::option::Option::Some(#args_ident),
+        }),
+        init_raw: Some(quote_spanned! {
+            span =>
+            ::core::ptr::addr_of_mut!((*#slot_ident)._args)
+                .write(::core::option::Option::Some(#args_ident));
+        }),
+        items: Some(quote_spanned! {
+            span =>
+            impl #impl_generics #struct_name #ty_generics #where_clause {
+                /// Clone the future, unless it was polled already
+                ///
+                /// The clone is created from a clone of the arguments,
+                /// as if the function was called again.
+                #[inline]
+                #[allow(dead_code)]
+                #struct_vis fn try_clone(&self) -> ::core::option::Option<Self> {
+                    let #args_ident = ::core::option::Option::as_ref(&self._args)?;
+                    ::core::option::Option::Some(<Self as #crate_name::machinery::NamedFuture>::new(
+                        ::core::clone::Clone::clone(#args_ident),
+                    ))
+                }
+            }
+
+            impl #impl_generics ::core::clone::Clone for #struct_name #ty_generics #where_clause {
+                /// Clone the future
+                ///
+                /// # Panics
+                ///
+                /// Panics if the future was polled already.
+                #[inline]
+                fn clone(&self) -> Self {
+                    match Self::try_clone(self) {
+                        ::core::option::Option::Some(#fut_ident) => #fut_ident,
+                        ::core::option::Option::None => ::core::panic!(#msg),
+                    }
+                }
+            }
+        }),
+    }
+}
+
+/// "type {Type}Shared = Shared<{Type}>;" and "fn {fn}_shared()"
+fn shared(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    struct_vis: &syn::Visibility,
+    func_vis: &syn::Visibility,
+    func_sig: &syn::Signature,
+    args_exprs_as_tuple: &syn::ExprTuple,
+) -> Option<TokenStream> {
+    let span = args.shared.as_ref()?.span();
+    let shared_name = format_ident!("{}Shared", struct_name);
+    let (_, ty_generics, _) = func_sig.generics.split_for_impl();
+
+    let mut shared_sig = func_sig.clone();
+    shared_sig.ident = format_ident!("{}_shared", func_sig.ident);
+    shared_sig.output = parse_quote_spanned! {
+        span => -> #shared_name #ty_generics
+    };
+    let type_doc = format!(" A [`{struct_name}`] that can be cloned, and awaited by many tasks");
+    let func_doc = format!(
+        " Like [`{}()`], but return a future that can be cloned",
+        func_sig.ident.unraw(),
+    );
+
+    Some(quote_spanned! {
+        span =>
+        #[doc = #type_doc]
+        #struct_vis type #shared_name #ty_generics = #crate_name::Shared<#struct_name #ty_generics>;
+
+        #[doc = #func_doc]
+        #[inline]
+        #[allow(clippy::needless_lifetimes)]
+        #func_vis #shared_sig {
+            #crate_name::Shared::new(
+                <#struct_name #ty_generics as #crate_name::machinery::NamedFuture>::
+                    new(#args_exprs_as_tuple),
+            )
+        }
+    })
+}
+
+/// "type {Type}Retry<P> = Retry<{Type}, P>;" and "fn {fn}_retry(…, policy: P)"
+fn retry(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    struct_vis: &syn::Visibility,
+    func_vis: &syn::Visibility,
+    func_sig: &syn::Signature,
+    args_exprs_as_tuple: &syn::ExprTuple,
+) -> Option<TokenStream> {
+    let span = args.retry.as_ref()?.span();
+    let mixed_site = Span::mixed_site();
+    let policy_ty = Ident::new("P", mixed_site);
+    let policy_ident = Ident::new("policy", mixed_site);
+    let retry_name = format_ident!("{}Retry", struct_name);
+    let (_, ty_generics, _) = func_sig.generics.split_for_impl();
+    let lifetimes = func_sig.generics.lifetimes().map(|param| &param.lifetime);
+
+    let mut retry_sig = func_sig.clone();
+    retry_sig.ident = format_ident!("{}_retry", func_sig.ident);
+    retry_sig
+        .generics
+        .params
+        .push(parse_quote_spanned!(span => #policy_ty));
+    retry_sig
+        .generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote_spanned! {
+            span =>
+            #policy_ty: #crate_name::RetryPolicy<
+                #crate_name::machinery::RetryError<#struct_name #ty_generics>,
+            >
+        });
+    retry_sig
+        .inputs
+        .push(parse_quote_spanned!(span => #policy_ident: #policy_ty));
+    let (_, retry_ty_generics, _) = retry_sig.generics.split_for_impl();
+    retry_sig.output = parse_quote_spanned! {
+        span => -> #retry_name #retry_ty_generics
+    };
+    let type_doc = format!(" A [`{struct_name}`] that is retried according to the policy `P`");
+    let func_doc = format!(
+        " Like [`{}()`], but retry failed attempts according to `policy`",
+        func_sig.ident.unraw(),
+    );
+
+    Some(quote_spanned! {
+        span =>
+        #[doc = #type_doc]
+        #struct_vis type #retry_name<#(#lifetimes,)* #policy_ty> =
+            #crate_name::Retry<#struct_name #ty_generics, #policy_ty>;
+
+        #[doc = #func_doc]
+        #[inline]
+        #[allow(clippy::needless_lifetimes)]
+        #func_vis #retry_sig {
+            #crate_name::Retry::new(#args_exprs_as_tuple, #policy_ident)
+        }
+    })
+}
+
+/// "type {Type}Timeout<S> = Timeout<{Type}, S>;" and "fn {fn}_with_deadline(…, deadline: S)"
+fn timeout(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    struct_vis: &syn::Visibility,
+    func_vis: &syn::Visibility,
+    func_sig: &syn::Signature,
+    args_exprs_as_tuple: &syn::ExprTuple,
+) -> Option<TokenStream> {
+    let span = args.timeout.as_ref()?.span();
+    let mixed_site = Span::mixed_site();
+    let deadline_ty = Ident::new("S", mixed_site);
+    let deadline_ident = Ident::new("deadline", mixed_site);
+    let timeout_name = format_ident!("{}Timeout", struct_name);
+    let (_, ty_generics, _) = func_sig.generics.split_for_impl();
+    let lifetimes = func_sig.generics.lifetimes().map(|param| &param.lifetime);
+
+    let mut timeout_sig = func_sig.clone();
+    timeout_sig.ident = format_ident!("{}_with_deadline", func_sig.ident);
+    timeout_sig
+        .generics
+        .params
+        .push(parse_quote_spanned!(span => #deadline_ty));
+    timeout_sig
+        .generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote_spanned! {
+            span => #deadline_ty: ::core::future::Future<Output = ()>
+        });
+    timeout_sig
+        .inputs
+        .push(parse_quote_spanned!(span => #deadline_ident: #deadline_ty));
+    let (_, timeout_ty_generics, _) = timeout_sig.generics.split_for_impl();
+    timeout_sig.output = parse_quote_spanned! {
+        span => -> #timeout_name #timeout_ty_generics
+    };
+    let type_doc = format!(" A [`{struct_name}`] that fails if the deadline `S` completes first");
+    let func_doc = format!(
+        " Like [`{}()`], but return `Err(Elapsed)` if `deadline` completes first",
+        func_sig.ident.unraw(),
+    );
+
+    Some(quote_spanned! {
+        span =>
+        #[doc = #type_doc]
+        #struct_vis type #timeout_name<#(#lifetimes,)* #deadline_ty> =
+            #crate_name::Timeout<#struct_name #ty_generics, #deadline_ty>;
+
+        #[doc = #func_doc]
+        #[inline]
+        #[allow(clippy::needless_lifetimes)]
+        #func_vis #timeout_sig {
+            #crate_name::Timeout::new(
+                <#struct_name #ty_generics as #crate_name::machinery::NamedFuture>::
+                    new(#args_exprs_as_tuple),
+                #deadline_ident,
+            )
+        }
+    })
+}
+
+/// Turn the synchronous `fn` into an `async fn` that awaits its body on a `BlockingExecutor`
+fn spawn_blocking_body(
+    spawn_blocking: &config::SpawnBlocking,
+    crate_name: &syn::Path,
+    func: &mut config::Func,
+) {
+    let span = spawn_blocking.ident.span();
+    let executor = match &spawn_blocking.executor {
+        Some(executor) => quote_spanned!(span => &#executor),
+        None => quote_spanned!(span => #crate_name::ThreadPool::global()),
+    };
+    let output = match &func.sig.output {
+        syn::ReturnType::Default => quote_spanned!(span => ()),
+        syn::ReturnType::Type(_, ty) => ty.to_token_stream(),
+    };
+    let body = &func.body;
+    let stmts = quote_spanned! {
+        span =>
+        #crate_name::machinery::spawn_blocking(#executor, move || -> #output #body).await
+    };
+    let mut group = proc_macro2::Group::new(proc_macro2::Delimiter::Brace, stmts);
+    group.set_span(body.span());
+    func.body = TokenTree::Group(group);
+    func.sig.asyncness = Some(syn::Token![async](span));
+}
+
+/// "fn {fn}_blocking(…) -> T", which runs the future on the current thread
+fn blocking(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    func_vis: &syn::Visibility,
+    func_sig: &syn::Signature,
+    poll_output: &TokenStream,
+    args_exprs_as_tuple: &syn::ExprTuple,
+) -> Option<TokenStream> {
+    let span = args.blocking.as_ref()?.span();
+    let (_, ty_generics, _) = func_sig.generics.split_for_impl();
+
+    let mut blocking_sig = func_sig.clone();
+    blocking_sig.ident = format_ident!("{}_blocking", func_sig.ident);
+    blocking_sig.output = parse_quote_spanned!(span => -> #poll_output);
+    let func_doc = format!(
+        " Like [`{}()`], but block the current thread until the future completed",
+        func_sig.ident.unraw(),
+    );
+
+    Some(quote_spanned! {
+        span =>
+        #[doc = #func_doc]
+        #[inline]
+        #[allow(clippy::needless_lifetimes)]
+        #func_vis #blocking_sig {
+            #crate_name::block_on(
+                <#struct_name #ty_generics as #crate_name::machinery::NamedFuture>::
+                    new(#args_exprs_as_tuple),
+            )
+        }
+    })
+}
+
+/// "struct {Type}Service", a `tower::Service` that calls the function, and "struct {Type}Layer"
+///
+/// The last argument of the function is the request. The optional first argument is the state of
+/// the service, which is cloned for every call. With `layer`, the state is the inner service.
+fn service(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    struct_vis: &syn::Visibility,
+    func_sig: &syn::Signature,
+    poll_output: &TokenStream,
+) -> syn::Result<Option<TokenStream>> {
+    let Some(service) = &args.service else {
+        return Ok(None);
+    };
+    let span = service.span();
+    let mixed_site = Span::mixed_site();
+    let state_ident = Ident::new("state", mixed_site);
+    let req_ident = Ident::new("req", mixed_site);
+    let service_name = format_ident!("{}Service", struct_name);
+    let layer_name = format_ident!("{}Layer", struct_name);
+
+    let mut errors = None;
+    if let Some(param) = func_sig.generics.lifetimes().next() {
+        let msg = "`service` cannot be used with lifetime parameters, \
+            because the future of a `tower::Service` cannot borrow from the service";
+        config::combine_errors(&mut errors, syn::Error::new_spanned(param, msg));
+    }
+    let types = func_sig
+        .inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(arg) => Some(&*arg.ty),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let (state_ty, req_ty) = match types.as_slice() {
+        [req_ty] => (None, *req_ty),
+        [state_ty, req_ty] => (Some(*state_ty), *req_ty),
+        _ => {
+            let msg = "`service` expects the arguments `(state, request)` or `(request)`";
+            let err = syn::Error::new(func_sig.paren_token.span.join(), msg);
+            config::combine_errors(&mut errors, err);
+            return Err(errors.unwrap());
+        },
+    };
+    if let (Some(layer), None) = (&args.layer, state_ty) {
+        let msg = "`layer` passes the inner service as the first argument, which is missing";
+        config::combine_errors(&mut errors, syn::Error::new(layer.span(), msg));
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let service_doc = format!(
+        " A `tower::Service` that calls [`{}()`] for every request",
+        func_sig.ident.unraw(),
+    );
+    let (service_struct, args_expr) = if let Some(state_ty) = state_ty {
+        let new_doc = format!(
+            " A service that passes a clone of `state` to every call of [`{}()`]",
+            func_sig.ident.unraw(),
+        );
+        let service_struct = quote_spanned! {
+            span =>
+            #[doc = #service_doc]
+            #[derive(Clone)]
+            #struct_vis struct #service_name {
+                #state_ident: #state_ty,
+            }
+
+            impl #service_name {
+                #[doc = #new_doc]
+                #[inline]
+                #[must_use]
+                #struct_vis fn new(#state_ident: #state_ty) -> Self {
+                    Self { #state_ident }
+                }
+            }
+
+            impl ::core::fmt::Debug for #service_name {
+                fn fmt(
+                    &self,
+                    f: &mut ::core::fmt::Formatter<'_>,
+                ) -> ::core::result::Result<(), ::core::fmt::Error> {
+                    f.debug_struct(::core::stringify!(#service_name)).finish_non_exhaustive()
+                }
+            }
+        };
+        let args_expr = quote_spanned! {
+            span => (::core::clone::Clone::clone(&self.#state_ident), #req_ident)
+        };
+        (service_struct, args_expr)
+    } else {
+        let service_struct = quote_spanned! {
+            span =>
+            #[doc = #service_doc]
+            #[derive(Debug, Clone, Copy, Default)]
+            #struct_vis struct #service_name;
+        };
+        (service_struct, quote_spanned!(span => (#req_ident,)))
+    };
+
+    let layer_items = args.layer.as_ref().map(|layer| {
+        let span = layer.span();
+        let layer_doc = format!(" A `tower::Layer` that wraps a service in a [`{service_name}`]");
+        quote_spanned! {
+            span =>
+            #[doc = #layer_doc]
+            #[derive(Debug, Clone, Copy, Default)]
+            #struct_vis struct #layer_name;
+
+            impl #crate_name::machinery::Layer<#state_ty> for #layer_name {
+                type Service = #service_name;
+
+                #[inline]
+                fn layer(&self, #state_ident: #state_ty) -> #service_name {
+                    #service_name::new(#state_ident)
+                }
+            }
+        }
+    });
+
+    Ok(Some(quote_spanned! {
+        span =>
+        #service_struct
+
+        impl #crate_name::machinery::Service<#req_ty> for #service_name {
+            type Response = <#poll_output as #crate_name::machinery::IsResult>::Ok;
+            type Error = <#poll_output as #crate_name::machinery::IsResult>::Err;
+            type Future = #struct_name;
+
+            #[inline]
+            fn poll_ready(
+                &mut self,
+                _: &mut ::core::task::Context<'_>,
+            ) -> ::core::task::Poll<::core::result::Result<(), Self::Error>> {
+                ::core::task::Poll::Ready(::core::result::Result::Ok(()))
+            }
+
+            #[inline]
+            fn call(&mut self, #req_ident: #req_ty) -> #struct_name {
+                <#struct_name as #crate_name::machinery::NamedFuture>::new(#args_expr)
+            }
+        }
+
+        #layer_items
+    }))
+}
+
+/// Remove the attributes `#[default]` and `#[default = expr]` from the arguments
+///
+/// Returns the default value of every argument, if it has one.
+fn take_defaults(args: &Args, sig: &mut syn::Signature) -> syn::Result<Vec<Option<syn::Expr>>> {
+    let mut errors = None;
+    let mut defaults = Vec::with_capacity(sig.inputs.len());
+    for input in &mut sig.inputs {
+        let syn::FnArg::Typed(arg) = input else {
+            defaults.push(None);
+            continue;
+        };
+        let mut default = None;
+        let mut result = Ok(());
+        arg.attrs.retain(|attr| {
+            if !attr.path().is_ident("default") {
+                return true;
+            }
+            let span = attr.path().span();
+            let value = match &attr.meta {
+                _ if args.builder.is_none() => Err(syn::Error::new(
+                    span,
+                    "`#[default]` requires `#[named_future(builder)]`",
+                )),
+                _ if default.is_some() => Err(syn::Error::new(span, "duplicate `#[default]`")),
+                syn::Meta::Path(_) => Ok(parse_quote_spanned! {
+                    span => ::core::default::Default::default()
+                }),
+                syn::Meta::NameValue(kv) => Ok(kv.value.clone()),
+                syn::Meta::List(list) => Err(syn::Error::new(
+                    list.delimiter.span().open(),
+                    "expected `#[default]` or `#[default = …]`",
+                )),
+            };
+            match value {
+                Ok(value) => default = Some(value),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                },
+            }
+            false
+        });
+        if let Err(err) = result {
+            config::combine_errors(&mut errors, err);
+        }
+        defaults.push(default);
+    }
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(defaults),
+    }
+}
+
+/// "struct {Type}Builder" with one setter per argument, and "fn {fn}() -> {Type}Builder"
+#[allow(clippy::too_many_arguments)]
+fn builder(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name: &Ident,
+    struct_vis: &syn::Visibility,
+    func_vis: &syn::Visibility,
+    func_sig: &syn::Signature,
+    defaults: &[Option<syn::Expr>],
+) -> Option<TokenStream> {
+    let span = args.builder.as_ref()?.span();
+    let mixed_site = Span::mixed_site();
+    let phantom_ident = Ident::new("_phantom", mixed_site);
+    let builder_name = format_ident!("{}Builder", struct_name);
+    let (_, ty_generics, _) = func_sig.generics.split_for_impl();
+    let unset: syn::Type = parse_quote_spanned!(span => #crate_name::machinery::Unset);
+
+    let fields = func_sig
+        .inputs
+        .iter()
+        .zip(defaults)
+        .filter_map(|(input, default)| match input {
+            syn::FnArg::Typed(syn::PatType { pat, ty, .. }) => match &**pat {
+                syn::Pat::Ident(pat) => Some((pat.ident.clone(), &**ty, default.as_ref())),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>();
+    // Every required argument gets a type parameter, which is `Unset` or `Set<T>`.
+    let states = fields
+        .iter()
+        .filter(|(_, _, default)| default.is_none())
+        .enumerate()
+        .map(|(index, (ident, _, _))| (ident, Ident::new(&format!("U{index}"), mixed_site)))
+        .collect::<Vec<_>>();
+    let state_of = |ident: &Ident| {
+        states
+            .iter()
+            .find(|(name, _)| *name == ident)
+            .map(|(_, state)| state)
+    };
+
+    let (impl_generics, _, where_clause) = func_sig.generics.split_for_impl();
+    let mut struct_generics = func_sig.generics.clone();
+    let mut builder_generics = func_sig.generics.clone();
+    for (_, state) in &states {
+        struct_generics
+            .params
+            .push(parse_quote_spanned!(span => #state = #unset));
+        builder_generics
+            .params
+            .push(parse_quote_spanned!(span => #state));
+    }
+    let (builder_impl_generics, builder_ty_generics, _) = builder_generics.split_for_impl();
+    let lifetimes = func_sig
+        .generics
+        .lifetimes()
+        .map(|param| &param.lifetime)
+        .collect::<Vec<_>>();
+    let field_idents = fields.iter().map(|(ident, _, _)| ident).collect::<Vec<_>>();
+
+    let field_defs = fields.iter().map(|(ident, ty, _)| match state_of(ident) {
+        Some(state) => quote_spanned!(span => #ident: #state,),
+        None => quote_spanned!(span => #ident: #ty,),
+    });
+    let field_inits = fields.iter().map(|(ident, _, default)| match default {
+        Some(default) => quote_spanned!(span => #ident: #default,),
+        None => quote_spanned!(span => #ident: #unset,),
+    });
+    let setters = fields.iter().map(|(ident, ty, _)| {
+        let doc = format!(" Set the argument `{}`", ident.unraw());
+        if state_of(ident).is_none() {
+            return quote_spanned! {
+                span =>
+                #[doc = #doc]
+                #[inline]
+                #func_vis fn #ident(mut self, #ident: #ty) -> Self {
+                    self.#ident = #ident;
+                    self
+                }
+            };
+        };
+        let output_states = states.iter().map(|(name, other)| {
+            if *name == ident {
+                quote_spanned!(span => #crate_name::machinery::Set<#ty>)
+            } else {
+                other.to_token_stream()
+            }
+        });
+        let moved_fields = field_idents.iter().map(|&name| {
+            if name == ident {
+                quote_spanned!(span => #name: #crate_name::machinery::Set(#name),)
+            } else {
+                quote_spanned!(span => #name: self.#name,)
+            }
+        });
+        quote_spanned! {
+            span =>
+            #[doc = #doc]
+            #[inline]
+            #func_vis fn #ident(
+                self,
+                #ident: #ty,
+            ) -> #builder_name<#(#lifetimes,)* #(#output_states,)*> {
+                #builder_name {
+                    #(#moved_fields)*
+                    #phantom_ident: ::core::marker::PhantomData,
+                }
+            }
+        }
+    });
+    let set_states = fields.iter().filter_map(|(ident, ty, _)| {
+        state_of(ident).map(|_| quote_spanned!(span => #crate_name::machinery::Set<#ty>))
+    });
+    let arg_exprs = fields.iter().map(|(ident, _, _)| match state_of(ident) {
+        Some(_) => quote_spanned!(span => self.#ident.0,),
+        None => quote_spanned!(span => self.#ident,),
+    });
+
+    let mut builder_sig = func_sig.clone();
+    builder_sig.inputs = Punctuated::new();
+    builder_sig.output = parse_quote_spanned! {
+        span => -> #builder_name #ty_generics
+    };
+    let builder_doc = format!(
+        " Builder of a [`{struct_name}`], returned by [`{}()`]\n\n\
+        Await it, or call [`IntoFuture::into_future()`](::core::future::IntoFuture::into_future) \
+        after all required arguments were set.",
+        func_sig.ident.unraw(),
+    );
+
+    Some(quote_spanned! {
+        span =>
+        #[inline]
+        #[allow(clippy::needless_lifetimes)]
+        #func_vis #builder_sig {
+            #builder_name {
+                #(#field_inits)*
+                #phantom_ident: ::core::marker::PhantomData,
+            }
+        }
+
+        #[doc = #builder_doc]
+        #[must_use = "the builder does nothing unless you `.await` it"]
+        #struct_vis struct #builder_name #struct_generics #where_clause {
+            #(#field_defs)*
+            #phantom_ident: ::core::marker::PhantomData<fn() -> #struct_name #ty_generics>,
+        }
+
+        #[allow(clippy::needless_lifetimes)]
+        impl #builder_impl_generics #builder_name #builder_ty_generics #where_clause {
+            #(#setters)*
+        }
+
+        #[allow(clippy::needless_lifetimes)]
+        impl #impl_generics ::core::future::IntoFuture
+        for #builder_name<#(#lifetimes,)* #(#set_states,)*> #where_clause {
+            type Output = <#struct_name #ty_generics as ::core::future::Future>::Output;
+            type IntoFuture = #struct_name #ty_generics;
+
+            #[inline]
+            fn into_future(self) -> Self::IntoFuture {
+                <#struct_name #ty_generics as #crate_name::machinery::NamedFuture>::
+                    new((#(#arg_exprs)*))
+            }
+        }
+    })
+}
+
+/// Call the `on_cancel` callback if the future is dropped before it completed
+fn on_cancel(
+    args: &Args,
+    crate_name: &syn::Path,
+    struct_name_string: &str,
+    slot_expr: &TokenStream,
+) -> Option<TokenStream> {
+    let callback = args.on_cancel.as_ref()?;
+    let span = callback.span();
+    let call_args = match &args.sidecar {
+        Some(_) => quote_spanned!(span => #struct_name_string, &self._sidecar),
+        None => quote_spanned!(span => #struct_name_string),
+    };
+    Some(quote_spanned! {
+        span =>
+        if #crate_name::machinery::is_pending(#slot_expr) {
+            #callback(#call_args);
+        }
+    })
+}
+
+/// The parts of the expansion that depend on how the unnamed future is stored
+struct Backend {
+    /// Definition of the `type_alias_impl_trait` that names the unnamed future
+    opaque_definition: Option<TokenStream>,
+    /// Type of the field that stores the unnamed future
+    storage: TokenStream,
+    /// `unsafe impl Declared`, if the struct was declared by the user
+    impl_declared: Option<TokenStream>,
+    /// Definition of the generator function
+    gen_body: TokenStream,
+    /// Body of `NamedFuture::new()`
+    new_body: TokenStream,
+    /// Body of `NamedFuture::init_raw()`
+    init_body: TokenStream,
+    /// `impl Drop`, if needed
+    impl_drop: Option<TokenStream>,
+    /// Body of `Future::poll()`
+    poll_body: TokenStream,
+}
+
+fn gen_sig(
+    func: &config::Func,
+    gen_ident: &Ident,
+    args_pats_as_tuple: &syn::Pat,
+    arg_types_as_tuple: &syn::Type,
+) -> syn::Signature {
+    let function_name_span = func.sig.ident.span();
+
+    let mut gen_sig = func.sig.clone();
+    gen_sig.ident = gen_ident.clone();
+    gen_sig.inputs = parse_quote_spanned! {
+        function_name_span => #args_pats_as_tuple: #arg_types_as_tuple,
+    };
+    gen_sig
+}
+
+/// `PhantomData` that makes the generated struct exactly as variant as the unnamed future
+///
+/// The future owns its arguments, and produces its output, so the variance of the lifetimes is
+/// derived from these types. E.g. an argument `&'a mut &'b u32` makes the struct invariant in `'b`.
+/// Every lifetime is mentioned covariantly, too, so that unused lifetimes are accepted.
+fn phantom(
+    func_gen: &syn::Generics,
+    arg_types_as_tuple: &syn::Type,
+    func_output: &syn::Type,
+    function_name_span: proc_macro2::Span,
+) -> syn::Type {
+    let mut result = Punctuated::<syn::Type, syn::Token![,]>::new();
+    result.push(arg_types_as_tuple.clone());
+    result.push(parse_quote_spanned!(function_name_span => fn() -> #func_output));
+    for ty in func_gen.type_params() {
+        let ty = &ty.ident;
+        result.push(parse_quote_spanned!(function_name_span => fn() -> *const #ty));
+    }
+    for syn::LifetimeParam { lifetime, .. } in func_gen.lifetimes() {
+        result.push(parse_quote_spanned!(function_name_span => &#lifetime ()));
+    }
+    result.push_punct(Default::default());
+    let result = syn::Type::Tuple(syn::TypeTuple {
+        paren_token: Default::default(),
+        elems: result,
+    });
+    parse_quote_spanned! {
+        function_name_span => ::core::marker::PhantomData<#result>
+    }
+}
+
+/// "impl Send for Type {}"
+fn impl_send(
+    args: &config::Args,
+    struct_name: &Ident,
+    func_gen: &syn::Generics,
+    gen_ident: &Ident,
+    crate_name: &syn::Path,
+) -> Option<TokenStream> {
+    let span = args.send.as_ref().filter(|flag| flag.enabled)?.ident.span();
+    let (impl_generics, ty_generics, where_clause) = func_gen.split_for_impl();
+    Some(quote_spanned! {
+        span =>
+        const _: () = #crate_name::machinery::ensure_send(&#gen_ident);
+
+        unsafe impl #impl_generics ::core::marker::Send
+        for #struct_name #ty_generics #where_clause {}
+    })
+}
+
+/// "impl Sync for Type {}"
+fn impl_sync(
+    args: &config::Args,
+    struct_name: &Ident,
+    func_gen: &syn::Generics,
+    gen_ident: &Ident,
+    crate_name: &syn::Path,
+) -> Option<TokenStream> {
+    let span = args.sync.as_ref().filter(|flag| flag.enabled)?.ident.span();
+    let (impl_generics, ty_generics, where_clause) = func_gen.split_for_impl();
+    Some(quote_spanned! {
+        span =>
+        const _: () = #crate_name::machinery::ensure_sync(&#gen_ident);
+
+        unsafe impl #impl_generics ::core::marker::Sync
+        for #struct_name #ty_generics #where_clause {}
+    })
+}
+
+/// "assert!(SIZE_OF <= max_size)"
+fn assert_max_size(
+    args: &config::Args,
+    struct_name: &Ident,
+    ty_self: &TokenStream,
+    crate_name: &syn::Path,
+) -> Option<TokenStream> {
+    let max_size = args.max_size.as_ref()?;
+    let span = max_size.span();
+    let msg = format!("`{struct_name}` exceeds its size budget of {max_size} bytes");
+    Some(quote_spanned! {
+        span =>
+        const _: () = ::core::assert!(
+            <#ty_self as #crate_name::machinery::NamedFuture>::SIZE_OF <= #max_size,
+            #msg,
+        );
+    })
+}
+
+/// Comma separated expression "a, b, c"
+fn arg_exprs_with_commas(
+    func: &config::Func,
+) -> syn::Result<Punctuated<syn::Expr, syn::token::Comma>> {
+    let mut result = func
+        .sig
+        .inputs
+        .iter()
+        .map(|input| {
+            if let syn::FnArg::Typed(item) = input {
+                let span = item.span();
+                let mut pat = (*item.pat).clone();
+                if let syn::Pat::Ident(pat) = &mut pat {
+                    pat.mutability = None;
+                }
+                let expr: syn::Expr = parse_quote_spanned!(span => #pat);
+                Ok(expr)
+            } else {
+                Err(syn::Error::new_spanned(input, "Not implemented for Self"))
+            }
+        })
+        .collect::<syn::Result<Punctuated<_, syn::Token![,]>>>()?;
+    if !result.is_empty() && !result.trailing_punct() {
+        result.push_punct(Default::default());
+    }
+    Ok(result)
+}
+
+/// A type "(A, B, C)"
+fn arg_types_as_tuple(func: &config::Func) -> syn::Result<syn::Type> {
+    let mut result = func
+        .sig
+        .inputs
+        .iter()
+        .map(|input| {
+            if let syn::FnArg::Typed(item) = input {
+                Ok(syn::Type::clone(&item.ty))
+            } else {
+                Err(syn::Error::new_spanned(input, "Not implemented for Self"))
+            }
+        })
+        .collect::<syn::Result<Punctuated<_, syn::Token![,]>>>()?;
+    if !result.is_empty() && !result.trailing_punct() {
+        result.push_punct(Default::default());
+    }
+
+    Ok(syn::Type::Tuple(syn::TypeTuple {
+        paren_token: Default::default(),
+        elems: result,
+    }))
+}
+
+/// A pattern "(a, b, c)"
+fn args_pats_as_tuple(func: &config::Func) -> syn::Result<syn::Pat> {
+    let mut result = func
+        .sig
+        .inputs
+        .iter()
+        .map(|input| {
+            if let syn::FnArg::Typed(item) = input {
+                let mut pat = (*item.pat).clone();
+                if let syn::Pat::Ident(pat) = &mut pat {
+                    pat.mutability = None;
+                }
+                Ok(pat)
+            } else {
+                Err(syn::Error::new_spanned(input, "Not implemented for Self"))
+            }
+        })
+        .collect::<syn::Result<Punctuated<_, syn::Token![,]>>>()?;
+    if !result.is_empty() && !result.trailing_punct() {
+        result.push_punct(Default::default());
+    }
+
+    Ok(syn::Pat::Tuple(syn::PatTuple {
+        attrs: vec![],
+        paren_token: Default::default(),
+        elems: result,
+    }))
+}
+
+/// The type "Self<'static, …>"
+fn ty_self(func: &config::Func, name: &Ident) -> TokenStream {
+    let lifetimes: Vec<_> = func
+        .sig
+        .generics
+        .lifetimes()
+        .map(|lt| &lt.lifetime.ident)
+        .collect();
+
+    let span = name.span();
+    let (_, ty_generics, _) = func.sig.generics.split_for_impl();
+    let stream = quote_spanned!(span => #name #ty_generics);
+
+    lifetimes_to_static(&lifetimes, stream)
+}
+
+/// Replace lifetimes in `lifetimes` with `'static`
+fn lifetimes_to_static(lifetimes: &[&Ident], stream: TokenStream) -> TokenStream {
+    let mut had_apos = false;
+    stream
+        .into_iter()
+        .map(|tt| {
+            let is_lt = had_apos;
+            had_apos = false;
+            match tt {
+                TokenTree::Ident(mut ident) => {
+                    if is_lt && lifetimes.iter().any(|lt| **lt == ident) {
+                        ident = Ident::new("static", ident.span());
+                    }
+                    TokenTree::Ident(ident)
+                },
+                TokenTree::Punct(punct) => {
+                    if punct.as_char() == '\'' {
+                        had_apos = true;
+                    }
+                    TokenTree::Punct(punct)
+                },
+                TokenTree::Literal(lit) => TokenTree::Literal(lit),
+                TokenTree::Group(group) => {
+                    let delimiter = group.delimiter();
+                    let tt = lifetimes_to_static(lifetimes, group.stream());
+                    TokenTree::Group(proc_macro2::Group::new(delimiter, tt))
+                },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,192 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use toml::value::{Table, Value};
+
+const PACKAGE_NAME: &str = "named-future";
+
+const DEPENDENCY_KINDS: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Information gathered from the `Cargo.toml` of the crate that invokes the macro
+#[derive(Debug)]
+pub(crate) struct Manifest {
+    /// Name of `named-future` in the caller's crate, if it is a direct dependency
+    pub(crate) crate_name: Option<String>,
+    /// Crate-wide defaults read from `[package.metadata.named-future]`
+    pub(crate) defaults: Result<Defaults, String>,
+}
+
+/// Crate-wide defaults for the arguments of `#[named_future]`
+#[derive(Debug, Default)]
+pub(crate) struct Defaults {
+    /// `Send = true`
+    pub(crate) send: bool,
+    /// `Sync = true`
+    pub(crate) sync: bool,
+    /// `type = "{Fn}Future"`
+    pub(crate) name: Option<String>,
+    /// `crate = "some::path"`
+    pub(crate) crate_name: Option<String>,
+    /// `max_size = 4096`
+    pub(crate) max_size: Option<u64>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            crate_name: None,
+            defaults: Ok(Defaults::default()),
+        }
+    }
+}
+
+impl Manifest {
+    /// Read the manifest of the crate that is currently being compiled
+    ///
+    /// The result is cached per manifest directory.
+    /// Any error while reading the manifest results in an empty [`Manifest`].
+    pub(crate) fn current() -> Rc<Manifest> {
+        thread_local! {
+            static CACHE: RefCell<HashMap<PathBuf, Rc<Manifest>>> = RefCell::default();
+        }
+
+        let Some(dir) = std::env::var_os("CARGO_MANIFEST_DIR") else {
+            return Rc::default();
+        };
+        let dir = PathBuf::from(dir);
+        CACHE.with(|cache| {
+            Rc::clone(
+                cache
+                    .borrow_mut()
+                    .entry(dir)
+                    .or_insert_with_key(|dir| Rc::new(Manifest::read(dir).unwrap_or_default())),
+            )
+        })
+    }
+
+    fn read(dir: &Path) -> Option<Manifest> {
+        let manifest = read_toml(&dir.join("Cargo.toml"))?;
+        Some(Manifest {
+            crate_name: find_crate_name(dir, &manifest),
+            defaults: read_defaults(&manifest),
+        })
+    }
+}
+
+/// Read `[package.metadata.named-future]`
+fn read_defaults(manifest: &Table) -> Result<Defaults, String> {
+    let mut defaults = Defaults::default();
+    let Some(metadata) = manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get(PACKAGE_NAME))
+    else {
+        return Ok(defaults);
+    };
+    let Some(metadata) = metadata.as_table() else {
+        return Err(format!("`package.metadata.{PACKAGE_NAME}` must be a table"));
+    };
+
+    for (key, value) in metadata {
+        let expected = match (key.as_str(), value) {
+            ("Send", Value::Boolean(value)) => {
+                defaults.send = *value;
+                continue;
+            },
+            ("Sync", Value::Boolean(value)) => {
+                defaults.sync = *value;
+                continue;
+            },
+            ("type", Value::String(value)) => {
+                defaults.name = Some(value.clone());
+                continue;
+            },
+            ("crate", Value::String(value)) => {
+                defaults.crate_name = Some(value.clone());
+                continue;
+            },
+            ("max_size", Value::Integer(value)) if *value >= 0 => {
+                defaults.max_size = Some(*value as u64);
+                continue;
+            },
+            ("Send" | "Sync", _) => "a boolean",
+            ("type" | "crate", _) => "a string",
+            ("max_size", _) => "a non-negative integer",
+            _ => {
+                return Err(format!(
+                    "unknown key `package.metadata.{PACKAGE_NAME}.{key}`, \
+                    expected one of `Send`, `Sync`, `type`, `crate`, or `max_size`",
+                ));
+            },
+        };
+        return Err(format!(
+            "`package.metadata.{PACKAGE_NAME}.{key}` must be {expected}"
+        ));
+    }
+    Ok(defaults)
+}
+
+/// Find the name under which `named-future` is imported
+///
+/// Returns `"named_future"` for the package itself, e.g. in its doctests.
+fn find_crate_name(dir: &Path, manifest: &Table) -> Option<String> {
+    let package_name = manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_str());
+    if package_name == Some(PACKAGE_NAME) {
+        return Some(PACKAGE_NAME.replace('-', "_"));
+    }
+
+    let targets = manifest
+        .get("target")
+        .and_then(|target| target.as_table())
+        .into_iter()
+        .flat_map(|targets| targets.values())
+        .filter_map(|target| target.as_table());
+    let mut workspace = None;
+    for table in std::iter::once(manifest).chain(targets) {
+        for kind in DEPENDENCY_KINDS {
+            let Some(deps) = table.get(*kind).and_then(|deps| deps.as_table()) else {
+                continue;
+            };
+            for (key, dep) in deps {
+                let is_workspace_dep = dep
+                    .get("workspace")
+                    .and_then(|workspace| workspace.as_bool())
+                    .unwrap_or(false);
+                let package = if is_workspace_dep {
+                    let workspace = workspace.get_or_insert_with(|| find_workspace(dir));
+                    workspace
+                        .as_ref()
+                        .and_then(|workspace| workspace.get("workspace"))
+                        .and_then(|workspace| workspace.get("dependencies"))
+                        .and_then(|deps| deps.get(key))
+                        .and_then(|dep| dep.get("package"))
+                } else {
+                    dep.get("package")
+                };
+                let package = package.and_then(|package| package.as_str()).unwrap_or(key);
+                if package == PACKAGE_NAME {
+                    return Some(key.replace('-', "_"));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Find the manifest of the workspace that contains `dir`
+fn find_workspace(dir: &Path) -> Option<Table> {
+    dir.ancestors().find_map(|dir| {
+        let manifest = read_toml(&dir.join("Cargo.toml"))?;
+        manifest.contains_key("workspace").then_some(manifest)
+    })
+}
+
+fn read_toml(path: &Path) -> Option<Table> {
+    let content = std::fs::read_to_string(path).ok()?;
+    toml::from_str(&content).ok()
+}
@@ -0,0 +1,247 @@
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::{fmt, task};
+
+/// The error of a future that was aborted, see <code>#\[named_future(abortable)\]</code>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Aborted;
+
+impl fmt::Display for Aborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the future was aborted")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for Aborted {}
+
+/// Create a connected pair of an [`AbortRegistration`] and an [`AbortHandle`]
+#[cfg(feature = "alloc")]
+#[must_use]
+pub fn abortable() -> (AbortRegistration, AbortHandle) {
+    let state = State::Owned(Arc::new(AbortState::new()));
+    (AbortRegistration(Some(state.clone())), AbortHandle(state))
+}
+
+/// Create a connected pair of an [`AbortRegistration`] and an [`AbortHandle`] that use `state`
+#[must_use]
+pub fn abortable_with(state: &'static AbortState) -> (AbortRegistration, AbortHandle) {
+    (
+        AbortRegistration(Some(State::Static(state))),
+        AbortHandle(State::Static(state)),
+    )
+}
+
+/// The state of an abortable future, which can be stored in a `static` without `alloc`
+///
+/// <code>#\[named_future(abortable)\]</code> generates `fn slow_mul_abortable_with(…, state)`,
+/// which takes a `&'static AbortState`. The state should not be shared by futures that run
+/// at the same time, because aborting one of them aborts all of them.
+///
+/// ```rust
+/// use named_future::AbortState;
+///
+/// static STATE: AbortState = AbortState::new();
+/// ```
+pub struct AbortState {
+    aborted: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl AbortState {
+    /// A state that was not aborted yet
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            aborted: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Clear the abort flag, so the state can be used for another future
+    #[inline]
+    pub fn reset(&self) {
+        self.aborted.store(false, Ordering::Release);
+    }
+
+    /// `true` if the future was aborted, and the state was not reset since
+    #[inline]
+    #[must_use]
+    pub fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Acquire)
+    }
+}
+
+impl Default for AbortState {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for AbortState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AbortState")
+            .field("aborted", &self.is_aborted())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An [`AbortState`] that is either reference counted or `'static`
+#[derive(Clone)]
+enum State {
+    #[cfg(feature = "alloc")]
+    Owned(Arc<AbortState>),
+    Static(&'static AbortState),
+}
+
+impl Deref for State {
+    type Target = AbortState;
+
+    #[inline]
+    fn deref(&self) -> &AbortState {
+        match self {
+            #[cfg(feature = "alloc")]
+            State::Owned(state) => state,
+            State::Static(state) => state,
+        }
+    }
+}
+
+/// The part of an abortable future that is stored in the generated struct
+///
+/// The default value belongs to a future that cannot be aborted.
+#[derive(Default)]
+pub struct AbortRegistration(Option<State>);
+
+impl AbortRegistration {
+    /// `true` if the future can be aborted, and [`AbortHandle::abort()`] was called
+    #[inline]
+    pub(crate) fn is_aborted(&self) -> bool {
+        match &self.0 {
+            Some(state) => state.is_aborted(),
+            None => false,
+        }
+    }
+
+    /// Wake the task of `cx` if the future is aborted after this call
+    #[inline]
+    pub(crate) fn register(&self, cx: &task::Context<'_>) {
+        if let Some(state) = &self.0 {
+            state.waker.register(cx.waker());
+        }
+    }
+}
+
+impl fmt::Debug for AbortRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AbortRegistration")
+            .field("aborted", &self.is_aborted())
+            .finish()
+    }
+}
+
+/// The part of an abortable future that is returned to the caller
+#[derive(Clone)]
+pub struct AbortHandle(State);
+
+impl AbortHandle {
+    /// Abort the future, and wake the task that polls it
+    #[inline]
+    pub fn abort(&self) {
+        self.0.aborted.store(true, Ordering::Release);
+        self.0.waker.wake();
+    }
+
+    /// `true` if [`abort()`](Self::abort) was called
+    #[inline]
+    #[must_use]
+    pub fn is_aborted(&self) -> bool {
+        self.0.is_aborted()
+    }
+}
+
+impl fmt::Debug for AbortHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AbortHandle")
+            .field("aborted", &self.is_aborted())
+            .finish()
+    }
+}
+
+/// A [`Waker`](task::Waker) that is registered by the future, and taken by the abort handle
+///
+/// Only the future registers a waker, so [`register()`](Self::register) is never called
+/// concurrently with itself, but it can race with any number of calls to [`wake()`](Self::wake).
+struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<task::Waker>>,
+}
+
+/// Nobody accesses the waker
+const WAITING: usize = 0;
+/// The future is replacing the waker
+const REGISTERING: usize = 1;
+/// An abort handle is taking the waker
+const WAKING: usize = 2;
+
+// SAFETY: the waker is only accessed by the party that moved the state away from `WAITING`.
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    fn register(&self, waker: &task::Waker) {
+        match self.state.compare_exchange(
+            WAITING,
+            REGISTERING,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                // SAFETY: we own the waker until the state is reset to `WAITING`
+                let slot = unsafe { &mut *self.waker.get() };
+                if !matches!(slot, Some(old) if old.will_wake(waker)) {
+                    *slot = Some(waker.clone());
+                }
+                let released = self.state.compare_exchange(
+                    REGISTERING,
+                    WAITING,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                );
+                if released.is_err() {
+                    // `wake()` was called in the meantime, and left the waking to us.
+                    let waker = slot.take();
+                    self.state.store(WAITING, Ordering::Release);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            },
+            // `wake()` is taking the old waker, so wake the new one ourselves.
+            Err(_) => waker.wake_by_ref(),
+        }
+    }
+
+    fn wake(&self) {
+        if self.state.fetch_or(WAKING, Ordering::AcqRel) == WAITING {
+            // SAFETY: we own the waker until the `WAKING` bit is cleared
+            let waker = unsafe { (*self.waker.get()).take() };
+            self.state.fetch_and(!WAKING, Ordering::Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
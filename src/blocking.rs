@@ -0,0 +1,47 @@
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::{future, pin};
+
+/// Run a future to completion on the current thread
+///
+/// This is a minimal executor: the thread is parked until the future wakes it up.
+/// It is used by the functions that <code>#\[named_future(blocking)\]</code> generates,
+/// so synchronous callers do not need an async runtime.
+///
+/// Do not call it from inside of an async task, because it blocks the thread of the executor.
+/// Futures that need a specific runtime, e.g. tokio's timers or sockets, still need that runtime.
+///
+/// ```rust
+/// use named_future::block_on;
+///
+/// assert_eq!(block_on(async { 6 * 7 }), 42);
+/// ```
+pub fn block_on<F: future::Future>(future: F) -> F::Output {
+    let mut future = future;
+    // SAFETY: `future` is shadowed, so it cannot be moved anymore
+    let mut future = unsafe { pin::Pin::new_unchecked(&mut future) };
+
+    let waker = Waker::from(Arc::new(Unparker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        // A wake-up before this call is not lost, because it unparks the thread in advance.
+        thread::park();
+    }
+}
+
+/// Wakes the thread that is running [`block_on()`]
+struct Unparker(Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
@@ -0,0 +1,243 @@
+use core::{fmt, marker};
+
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Lazy, NamedFuture};
+
+/// The name of the envelope struct
+const ENVELOPE: &str = "NamedFuture";
+/// The fields of the envelope struct, in this order
+const FIELDS: &[&str] = &["name", "args"];
+
+/// Serialize `args` in an envelope `{ name, args }`
+pub fn serialize_envelope<S, T>(
+    name: &'static str,
+    args: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize + ?Sized,
+{
+    let mut envelope = serializer.serialize_struct(ENVELOPE, FIELDS.len())?;
+    envelope.serialize_field(FIELDS[0], name)?;
+    envelope.serialize_field(FIELDS[1], args)?;
+    envelope.end()
+}
+
+/// A type that can be deserialized from the envelope of one of several named futures
+///
+/// Implemented by [`named_future_registry!`](crate::named_future_registry).
+pub trait Registry<'de>: Sized {
+    /// The names of the named futures
+    const NAMES: &'static [&'static str];
+
+    /// Deserialize the arguments of the named future `name`, which is an element of `NAMES`
+    fn deserialize_args<A: ArgsAccess<'de>>(
+        name: &'static str,
+        access: A,
+    ) -> Result<Self, A::Error>;
+}
+
+/// The `args` field of an envelope
+pub trait ArgsAccess<'de> {
+    /// The error type of the deserializer
+    type Error: de::Error;
+
+    /// Deserialize the arguments
+    fn args<T: Deserialize<'de>>(self) -> Result<T, Self::Error>;
+}
+
+/// Deserialize an envelope `{ name, args }`, and let `R` deserialize the arguments
+///
+/// In a self-describing format, the field `name` must precede the field `args`.
+pub fn deserialize_registry<'de, R, D>(deserializer: D) -> Result<R, D::Error>
+where
+    R: Registry<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_struct(ENVELOPE, FIELDS, EnvelopeVisitor(marker::PhantomData))
+}
+
+impl<F> Serialize for Lazy<F>
+where
+    F: NamedFuture,
+    F::Args: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.args() {
+            Some(args) => serialize_envelope(F::NAME, args, serializer),
+            None => Err(ser::Error::custom(format_args!(
+                "`{}` was started, so its arguments cannot be serialized",
+                F::NAME,
+            ))),
+        }
+    }
+}
+
+impl<'de, F> Deserialize<'de> for Lazy<F>
+where
+    F: NamedFuture,
+    F::Args: Deserialize<'de>,
+{
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_registry(deserializer)
+    }
+}
+
+impl<'de, F> Registry<'de> for Lazy<F>
+where
+    F: NamedFuture,
+    F::Args: Deserialize<'de>,
+{
+    const NAMES: &'static [&'static str] = &[F::NAME];
+
+    #[inline]
+    fn deserialize_args<A: ArgsAccess<'de>>(_: &'static str, access: A) -> Result<Self, A::Error> {
+        access.args().map(Self::new)
+    }
+}
+
+struct EnvelopeVisitor<R>(marker::PhantomData<fn() -> R>);
+
+impl<'de, R: Registry<'de>> Visitor<'de> for EnvelopeVisitor<R> {
+    type Value = R;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the envelope of a named future")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<R, A::Error> {
+        let Some(name) = seq.next_element_seed(NameSeed(R::NAMES))? else {
+            return Err(de::Error::invalid_length(0, &self));
+        };
+        R::deserialize_args(name, SeqArgs(seq))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<R, A::Error> {
+        match map.next_key::<Field>()? {
+            Some(Field::Name) => {},
+            Some(Field::Args) => return Err(de::Error::custom("`name` must precede `args`")),
+            None => return Err(de::Error::missing_field(FIELDS[0])),
+        }
+        let name = map.next_value_seed(NameSeed(R::NAMES))?;
+        R::deserialize_args(name, MapArgs(map))
+    }
+}
+
+/// Find the name in the list of registered names
+struct NameSeed(&'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for NameSeed {
+    type Value = &'static str;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl Visitor<'_> for NameSeed {
+    type Value = &'static str;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("one of ")?;
+        for (index, name) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "`{name}`")?;
+        }
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        match self.0.iter().find(|&&name| name == value) {
+            Some(name) => Ok(name),
+            None => Err(de::Error::invalid_value(de::Unexpected::Str(value), &self)),
+        }
+    }
+}
+
+enum Field {
+    Name,
+    Args,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl Visitor<'_> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("`name` or `args`")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Field, E> {
+        match value {
+            0 => Ok(Field::Name),
+            1 => Ok(Field::Args),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned(value),
+                &self,
+            )),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Field, E> {
+        match value {
+            "name" => Ok(Field::Name),
+            "args" => Ok(Field::Args),
+            _ => Err(de::Error::unknown_field(value, FIELDS)),
+        }
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Field, E> {
+        match value {
+            b"name" => Ok(Field::Name),
+            b"args" => Ok(Field::Args),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Bytes(value),
+                &self,
+            )),
+        }
+    }
+}
+
+struct SeqArgs<A>(A);
+
+impl<'de, A: SeqAccess<'de>> ArgsAccess<'de> for SeqArgs<A> {
+    type Error = A::Error;
+
+    fn args<T: Deserialize<'de>>(mut self) -> Result<T, Self::Error> {
+        match self.0.next_element()? {
+            Some(args) => Ok(args),
+            None => Err(de::Error::invalid_length(
+                1,
+                &"an envelope with two elements",
+            )),
+        }
+    }
+}
+
+struct MapArgs<A>(A);
+
+impl<'de, A: MapAccess<'de>> ArgsAccess<'de> for MapArgs<A> {
+    type Error = A::Error;
+
+    fn args<T: Deserialize<'de>>(mut self) -> Result<T, Self::Error> {
+        match self.0.next_key::<Field>()? {
+            Some(Field::Args) => self.0.next_value(),
+            Some(Field::Name) => Err(de::Error::duplicate_field(FIELDS[0])),
+            None => Err(de::Error::missing_field(FIELDS[1])),
+        }
+    }
+}
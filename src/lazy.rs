@@ -0,0 +1,102 @@
+use alloc::boxed::Box;
+use core::{fmt, future, mem, pin, task};
+
+use crate::NamedFuture;
+
+/// A named future that stores its arguments until it is polled for the first time
+///
+/// An `async fn` does nothing before it is polled, but its named future is `!Unpin`,
+/// and `!Send` unless <code>#\[named_future(Send)\]</code> was given, from the moment it was
+/// created. `Lazy` only holds <code>F::[Args](NamedFuture::Args)</code>, so it can be moved around
+/// freely before it is started. The first call to `poll()` constructs the named future in place
+/// on the heap with [`NamedFuture::boxed_pin()`].
+///
+/// `Lazy` is [`Unpin`] if the arguments are.
+/// It is [`Send`] if the arguments and the named future are, because a started future can be moved
+/// between two calls to `poll()`.
+///
+/// With the feature **`serde`**, `Lazy` implements `Serialize` and `Deserialize` if the arguments
+/// do. It is serialized as an envelope `{ name, args }`, see
+/// [`named_future_registry!`](crate::named_future_registry).
+///
+/// ```rust
+/// # #![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+/// use named_future::{Lazy, named_future};
+///
+/// #[named_future(Send)]
+/// async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+///     factor1 * factor2
+/// }
+///
+/// # fn main() {
+/// let future = Lazy::<SlowMul>::new((6, 7));
+/// # let _ = future;
+/// # }
+/// ```
+pub struct Lazy<F: NamedFuture> {
+    state: State<F>,
+}
+
+enum State<F: NamedFuture> {
+    /// The future was not polled yet
+    Args(F::Args),
+    /// The future was constructed
+    Started(pin::Pin<Box<F>>),
+    /// Constructing the future panicked
+    Empty,
+}
+
+impl<F: NamedFuture> Lazy<F> {
+    /// Store the arguments of the named future
+    #[inline]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub fn new(args: F::Args) -> Self {
+        Self {
+            state: State::Args(args),
+        }
+    }
+
+    /// `true` if the named future was constructed already
+    #[inline]
+    #[must_use]
+    pub fn is_started(&self) -> bool {
+        !matches!(self.state, State::Args(_))
+    }
+
+    /// The arguments, if the named future was not constructed yet
+    #[inline]
+    #[must_use]
+    pub fn args(&self) -> Option<&F::Args> {
+        match &self.state {
+            State::Args(args) => Some(args),
+            _ => None,
+        }
+    }
+}
+
+impl<F: NamedFuture> fmt::Debug for Lazy<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lazy")
+            .field("started", &self.is_started())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: NamedFuture + future::Future> future::Future for Lazy<F> {
+    type Output = F::Output;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // SAFETY: the arguments are never pinned, and the future is pinned on the heap
+        let this = unsafe { self.get_unchecked_mut() };
+        if let State::Args(_) = this.state {
+            let State::Args(args) = mem::replace(&mut this.state, State::Empty) else {
+                unreachable!();
+            };
+            this.state = State::Started(F::boxed_pin(args));
+        }
+        match &mut this.state {
+            State::Started(future) => future.as_mut().poll(cx),
+            _ => panic!("`Lazy` was polled after it panicked"),
+        }
+    }
+}
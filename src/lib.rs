@@ -41,6 +41,7 @@
 //! A simple workaround until [`#![feature(type_alias_impl_trait)]`][tait] is stabilized:
 //!
 //! ```rust
+//! # #![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
 //! # use named_future::named_future;
 //! /// A slow multiplication
 //! ///
@@ -54,6 +55,7 @@
 //! #   */
 //!     factor1 * factor2
 //! }
+//! # fn main() {}
 //! ```
 //!
 //! Expands to:
@@ -103,23 +105,194 @@
 //! - **`#[named_future(Sync)]`**  
 //!   - Implement [`Sync`] for the generated `struct`. Please see the explanation for `Send`.
 //!
+//! - **`#[named_future(!Send)]`**, **`#[named_future(!Sync)]`**  
+//!   - Do not implement [`Send`] or [`Sync`] even if it is enabled by the crate-wide defaults.
+//!
 //! - **<code>#\[named_future(type = <em>Name</em>)\]</code>**  
 //!   - Instead of the default name, i.e. using pascal case of the function name,
 //!     you can override the name using this argument.
-//!     You can also override the visibility of the `struct` using this argument: `Type = pub Name`.
+//!     You can also override the visibility of the `struct` using this argument: `type = pub Name`.
 //!     By default, the visibility of the function is copied.
 //!
 //! - **<code>#\[named_future(crate = <em>some::path</em>)\]</code>**  
-//!   - If you have renamed the dependency in your `Cargo.toml`,
-//!     e.g. `renamed = { package = "named-future", version = "0.0.1" }`,
+//!   - The path to this crate is read from your `Cargo.toml`, so a renamed dependency,
+//!     e.g. `renamed = { package = "named-future", version = "0.0.1" }`, works out of the box.
+//!     If you use `named-future` through a re-export of another crate,
 //!     then you have to specify its name / path.
-//!     Defaults to `::named_future`.
+//!     Defaults to `::named_future` if it is not a direct dependency.
+//!
+//! - **<code>#\[named_future(max_size = <em>bytes</em>)\]</code>**  
+//!   - Fail the compilation if the generated `struct` is larger than the given amount of bytes.
+//!
+//! - **<code>#\[named_future(into = <em>Name</em>)\]</code>**  
+//!   - Do not generate the `struct`, but use a `struct Name(Storage<Self>);` that you declared yourself,
+//!     annotated with [`#[derive(NamedFuture)]`](derive@NamedFuture).
+//!     This way you can add your own attributes and derives to the `struct`.
+//!
+//! - **`#[named_future(catch_unwind)]`**  
+//!   - Catch a panic of the future, and return it as `Err(payload)`.
+//!     The output of the future becomes `Result<T, [`PanicPayload`]>`,
+//!     and the unnamed future is dropped as soon as it panicked.
+//!     Requires the feature **`std`**.
+//!     Without this option a panic is propagated to the caller of `poll()`,
+//!     and polling the future again panics with a message like “`SlowMul` was polled after it panicked”.
+//!
+//! - **<code>#\[named_future(on_cancel = <em>path::to::callback</em>)\]</code>**  
+//!   - Call `callback(name: &'static str)` if the future is dropped before it completed,
+//!     e.g. to count cancellations, or to release external resources.
+//!     The callback is not called if the future completed or panicked.
+//!     It is called before the unnamed future is dropped.
+//!
+//! - **<code>#\[named_future(on_cancel = <em>callback</em>, sidecar = <em>Type</em>)\]</code>**  
+//!   - Store a `Type` in the generated `struct`, initialized with [`Default`],
+//!     and call `callback(name: &'static str, sidecar: &Type)` instead.
+//!     The sidecar can be accessed with the generated methods `sidecar()` and `sidecar_mut()`.
+//!
+//! - **`#[named_future(abortable)]`**  
+//!   - Also generate `fn slow_mul_abortable(…) -> (SlowMul, SlowMulAbortHandle)`.
+//!     `SlowMulAbortHandle::abort()` wakes the task that polls the future,
+//!     and the future returns `Err(`[`Aborted`]`)` when it is polled next.
+//!     The unnamed future is dropped in place at this point.
+//!     The output of the future becomes `Result<T, Aborted>`, also if it was created by `slow_mul()`,
+//!     which cannot be aborted.
+//!     `slow_mul_abortable()` requires the feature **`alloc`**.
+//!     `fn slow_mul_abortable_with(…, state: &'static AbortState)` works without it,
+//!     e.g. with a <code>static STATE: [AbortState] = AbortState::new();</code>.
+//!     Only atomic operations and no locks are used, so it works in `#![no_std]`.
+//!
+//! - **`#[named_future(shared)]`**  
+//!   - Also generate `type SlowMulShared = Shared<SlowMul>;`, and `fn slow_mul_shared(…) -> SlowMulShared`.
+//!     [`Shared`] is [`Clone`], polls the underlying future only once,
+//!     and hands a clone of its output to every clone.
+//!     The output must be `Clone`.
+//!     Requires the feature **`alloc`**.
+//!
+//! - **`#[named_future(Clone)]`**  
+//!   - Keep the arguments until the future is polled for the first time,
+//!     and implement [`Clone`] until then. All arguments must be `Clone`.
+//!     A clone is created from a clone of the arguments, as if the function was called again,
+//!     e.g. for fan-out or speculative execution of identical requests.
+//!     Cloning a future that was polled already panics,
+//!     so the generated method `try_clone()` returns `None` instead.
+//!     The unnamed future is created in place when it is polled for the first time.
+//!
+//! - **`#[named_future(retry)]`**  
+//!   - Also generate `type SlowMulRetry<P> = Retry<SlowMul, P>;`, and
+//!     `fn slow_mul_retry(…, policy: P) -> SlowMulRetry<P>`.
+//!     The function must return a `Result`, and all arguments must be `Clone`.
+//!     [`Retry`] recreates the future in place from a clone of the arguments after it returned an `Err`,
+//!     as long as the [`RetryPolicy`] allows it.
+//!     [`Attempts`] retries immediately, [`ExponentialBackoff`] sleeps with a [`Timer`] between attempts,
+//!     and a closure `FnMut(attempt, &error) -> bool` works, too.
+//!     Does not allocate, and works in `#![no_std]`.
+//!
+//! - **`#[named_future(timeout)]`**  
+//!   - Also generate `type SlowMulTimeout<S> = Timeout<SlowMul, S>;`, and
+//!     `fn slow_mul_with_deadline(…, deadline: S) -> SlowMulTimeout<S>`.
+//!     The deadline is any `Future<Output = ()>`, usually a [`Timer::sleep()`](Timer::sleep).
+//!     [`Timeout`] returns `Err(`[`Elapsed`]`)` if the deadline completes before the future.
+//!     `StdTimer` works with every executor, and spawns a thread per sleep (feature **`std`**).
+//!     `TokioTimer` (feature **`tokio`**) and `AsyncIoTimer` (feature **`async-io`**) use the timers
+//!     of these runtimes.
+//!
+//! - **`#[named_future(builder)]`**  
+//!   - `fn slow_mul()` takes no arguments, and returns a `SlowMulBuilder` with one setter per argument.
+//!     Arguments with an attribute `#[default = expr]`, or `#[default]` for `Default::default()`,
+//!     are optional. The builder implements `IntoFuture` with `IntoFuture = SlowMul`
+//!     after all required arguments were set, so `slow_mul().factor1(6).factor2(7).await` works.
+//!     Missing arguments are a compile time error.
+//!
+//! - **`#[named_future(blocking)]`**  
+//!   - Also generate `fn slow_mul_blocking(…) -> T`, which runs the future on the current thread
+//!     with [`block_on()`](block_on), a minimal executor that parks the thread until the future wakes it up.
+//!     Synchronous callers need no async runtime.
+//!     Requires the feature **`std`**.
+//!
+//! - **`#[named_future(spawn_blocking)]`**, **`#[named_future(spawn_blocking = path::to::EXECUTOR)]`**  
+//!   - Put this option on a synchronous `fn`, e.g. `fn hash_file(path: PathBuf) -> io::Result<Digest>`,
+//!     to get a named future `HashFile` that runs the body on another thread.
+//!     The body is spawned on the global [`ThreadPool`] when the future is polled for the first time,
+//!     or on the given [`BlockingExecutor`], e.g. a `static` `ThreadPool`.
+//!     The arguments and the output must be `Send + 'static`. A panic in the body is resumed in the
+//!     task that polls the future.
+//!     If the future is dropped before the body started, then the body is not run at all.
+//!     If the body is running already, then it runs to completion, and its output is dropped.
+//!     If the executor drops the body without running it, e.g. because no thread could be started,
+//!     then polling the future panics.
+//!     Requires the feature **`std`**.
+//!
+//! - **`#[named_future(service)]`**  
+//!   - Put this option on an `impl` block with one `async fn handle(&self, req: Req) -> Result<Resp, E>`
+//!     to generate a named future `Handle`, and a struct `HandleService` that holds the `Self` value,
+//!     and implements `tower::Service<Req>` with `type Future = Handle`,
+//!     so a `tower` middleware stack can name the future.
+//!     The service is created with `HandleService::new(value)`, and every call clones the value,
+//!     so `Self` must implement `Clone`, e.g. by wrapping its state in an `Arc`.
+//!     The `impl` block must not be generic, and other methods in it are left alone.
+//!     A free function `handle(value: Self, req: Req)` that calls the method is generated, too.
+//!   - On a free function `async fn handle(state: State, req: Req) -> Result<Resp, E>`,
+//!     the service holds the state instead, and a function `async fn handle(req: Req)` without
+//!     a state gets a unit struct `HandleService`.
+//!   - The service is always ready, the method or function must not have lifetime parameters,
+//!     and its output must be a `Result`.
+//!     Requires the feature **`tower`**.
+//!
+//! - **`#[named_future(layer)]`**  
+//!   - Together with `service`, generate a unit struct `HandleLayer` that implements `tower::Layer<State>`.
+//!     The state, or `Self`, is the inner service, which the function can call, e.g.
+//!     `async fn log(mut inner: ApiService, req: Request) -> Result<Response, Error>`.
+//!     Requires the feature **`tower`**.
+//!
+//! Crate-wide defaults for these arguments can be set in the `Cargo.toml` of your crate.
+//! An argument to `#[named_future]` overrides its default:
+//!
+//! ```toml
+//! [package.metadata.named-future]
+//! Send = true               # implement `Send` unless `!Send` was given
+//! Sync = false              # implement `Sync` unless `!Sync` was given
+//! type = "{Fn}Future"       # name template; `{Fn}` is pascal case, and `{fn}` is the function name
+//! crate = "facade::future"  # path to this crate
+//! max_size = 4096           # size budget in bytes
+//! ```
 //!
 //! To add a documentation to your function, and the generated struct,
 //! you can separate both sections with a line `/// # Struct`
 //!
+//! If you need to move a future around before it is started, e.g. to another thread,
+//! then you can wrap it in [`Lazy<SlowMul>`](Lazy), which only stores the arguments,
+//! and constructs the named future on the heap when it is polled for the first time.
+//! It requires the feature **`alloc`**.
+//!
+//! With the feature **`serde`**, an unstarted [`Lazy`] is serialized as an envelope `{ name, args }`,
+//! e.g. to queue work to disk, and resume it after a restart.
+//! [`named_future_registry!{…}`](named_future_registry) declares an `enum` of several named futures,
+//! which deserializes an envelope into the right variant.
+//!
 //! The library code can be used with `#![no_std]`.
 //!
+//! If you cannot or do not want to use proc macros, e.g. to keep the build times short,
+//! you can disable the default feature **`proc-macros`**, and use the declarative macro
+//! [`named_future_fn!{…}`](named_future_fn) instead, which generates the same code.
+//!
+//! If you want to generate named futures from your own proc macros, or from a `build.rs` script,
+//! then you can use the code generator [`named-future-codegen`](https://docs.rs/named-future-codegen)
+//! directly, without going through the attribute macro.
+//!
+//! If you use a nightly compiler, you can enable the feature **`nightly`**.
+//! Then the unnamed future is stored using [`#![feature(type_alias_impl_trait)]`][tait]
+//! instead of transmuting it into a byte array, with the same public API of the generated `struct`.
+//! The crate that uses `#[named_future]` has to enable `#![feature(type_alias_impl_trait)]`.
+//! Opaque types are invariant, so the generated `struct` is invariant over its lifetimes, too.
+//! This backend is meant to cross-check the soundness of the default implementation,
+//! and to ease the migration once the feature is stabilized.
+//!
+//! If `debug_assertions` are enabled, or if you enable the feature **`checked`**, then the
+//! generated code validates the assumptions of the unsafe machinery at runtime:
+//! it panics if a named future is moved after it was polled for the first time,
+//! if it is polled re-entrantly or after it was dropped,
+//! or if the size or alignment of its storage does not match the unnamed future.
+//! The panic messages name the affected future.
+//!
 //! Because of limitations in rust, it is currently not possible to implement a “named future” for
 //! generic functions:
 //! “error: generic `Self` types are currently not permitted in anonymous constants”.
@@ -148,10 +321,109 @@
 #![warn(unused_extern_crates)]
 #![warn(unused_lifetimes)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(target_has_atomic = "ptr")]
+mod abort;
+#[cfg(feature = "std")]
+mod blocking;
+#[cfg(feature = "serde")]
+mod envelope;
+#[cfg(feature = "alloc")]
+mod lazy;
 #[doc(hidden)]
 pub mod machinery;
+mod macros;
+mod retry;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+mod shared;
+#[cfg(feature = "std")]
+mod spawn_blocking;
+mod timeout;
+mod timer;
 
+#[cfg(target_has_atomic = "ptr")]
+pub use abort::{AbortState, Aborted};
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use blocking::block_on;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub use lazy::Lazy;
+pub use machinery::NamedFuture;
+/// Implement the glue for a user-declared `struct Name(Storage<Self>);`
+///
+/// Use this derive macro together with <code>#\[named_future(into = <em>Name</em>)\]</code>,
+/// if you want to have full control over the declaration of the generated struct,
+/// e.g. to add more derives, attributes, or documentation:
+///
+/// ```rust
+/// # #![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+/// use named_future::{named_future, NamedFuture, Storage};
+///
+/// /// Future generated by [`slow_mul`]
+/// #[derive(NamedFuture, Debug)]
+/// pub struct SlowMul(Storage<Self>);
+///
+/// /// A slow multiplication
+/// #[named_future(into = SlowMul)]
+/// pub async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+///     factor1 * factor2
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The struct must be a tuple struct with a single field of type [`Storage<Self>`].
+/// It may have lifetime parameters, which must be the same as the lifetime parameters of the
+/// function. `#[named_future(into = …)]` implements [`Future`](core::future::Future) and
+/// [`Drop`] for the struct, and [`Send`] and [`Sync`] if requested.
+/// Because the size of the storage depends on `Self`, the struct is invariant over its lifetimes.
+#[cfg(feature = "proc-macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "proc-macros")))]
+pub use named_future_proc_macros::NamedFuture;
 /// Please refer to the [crate level documentation](crate).
 #[cfg(feature = "proc-macros")]
 #[cfg_attr(docsrs, doc(cfg(feature = "proc-macros")))]
 pub use named_future_proc_macros::named_future;
+pub use retry::{Attempts, ExponentialBackoff, Retry, RetryDecision, RetryPolicy};
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub use shared::Shared;
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use spawn_blocking::{BlockingExecutor, BlockingTask, ThreadPool};
+pub use timeout::{Elapsed, Timeout};
+pub use timer::Timer;
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+pub use timer::TokioTimer;
+#[cfg(feature = "async-io")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-io")))]
+pub use timer::{AsyncIoSleep, AsyncIoTimer};
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use timer::{StdSleep, StdTimer};
+
+/// The payload of a panic that was caught by <code>#\[named_future(catch_unwind)\]</code>
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub type PanicPayload = std::boxed::Box<dyn core::any::Any + Send + 'static>;
+
+/// The storage of a named future in a user-declared struct
+///
+/// Please see [`#[derive(NamedFuture)]`](derive@NamedFuture).
+#[repr(transparent)]
+pub struct Storage<T: machinery::Declared> {
+    _slot: machinery::Slot<T::Bytes>,
+    _not_send_or_sync: core::marker::PhantomData<*mut ()>,
+    _pin: core::marker::PhantomPinned,
+}
+
+impl<T: machinery::Declared> core::fmt::Debug for Storage<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Storage").finish_non_exhaustive()
+    }
+}
@@ -1,4 +1,22 @@
-use core::{future, marker, mem, pin, ptr, task};
+use core::{fmt, future, marker, mem, pin, ptr, task};
+
+#[cfg(feature = "serde")]
+pub use serde;
+#[cfg(feature = "tower")]
+pub use tower_layer::Layer;
+#[cfg(feature = "tower")]
+pub use tower_service::Service;
+
+#[doc(hidden)]
+pub use crate::__named_future_if_alloc as if_alloc;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+pub use crate::abort::abortable;
+#[cfg(target_has_atomic = "ptr")]
+pub use crate::abort::{AbortHandle, AbortRegistration, abortable_with};
+#[cfg(feature = "serde")]
+pub use crate::envelope::{ArgsAccess, Registry, deserialize_registry, serialize_envelope};
+#[cfg(feature = "std")]
+pub use crate::spawn_blocking::{BlockingJob, spawn_blocking};
 
 mod align {
     pub trait Aligner {
@@ -27,8 +45,16 @@ mod align {
     impl_alignments!(1 2 4 8 16 32 64 128 256 512 1024 2048 4096 8192 16384 32768 65536);
 }
 
-#[doc(hidden)]
+/// A named future, implemented by [`#[named_future]`](crate)
+///
+/// Besides [`new()`](NamedFuture::new), which returns the future by value, the future can be
+/// constructed directly in its final location, e.g. on the heap or in a slot of an executor.
+/// With optimizations, this avoids copying large futures around on the stack. Without them,
+/// e.g. in debug builds, the compiler still creates the future on the stack before writing it.
 pub trait NamedFuture {
+    /// The name of the struct, e.g. `"SlowMul"`
+    const NAME: &'static str;
+
     /// Size of the future
     const SIZE_OF: usize;
 
@@ -40,8 +66,138 @@ pub trait NamedFuture {
 
     /// Build the named future
     fn new(args: Self::Args) -> Self;
+
+    /// Build the named future in place
+    ///
+    /// The signature matches `PinInit::__pinned_init()` of [`pin-init`], so the function can be
+    /// used as an initializer in pin-init-style APIs.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must be valid for writes and properly aligned. The written future must be treated as
+    /// pinned: it must not be moved, and it must be dropped before its memory is reused.
+    ///
+    /// [`pin-init`]: https://docs.rs/pin-init
+    unsafe fn init_raw(slot: *mut Self, args: Self::Args);
+
+    /// Build the named future in the pinned `slot`
+    ///
+    /// # Safety
+    ///
+    /// The future is not dropped automatically when `slot` goes out of scope.
+    /// It must be dropped with [`ptr::drop_in_place()`] before the memory of `slot` is reused,
+    /// unless it was never polled.
+    #[inline]
+    unsafe fn init_in(
+        slot: pin::Pin<&mut mem::MaybeUninit<Self>>,
+        args: Self::Args,
+    ) -> pin::Pin<&mut Self>
+    where
+        Self: Sized,
+    {
+        unsafe {
+            let slot = slot.get_unchecked_mut();
+            Self::init_raw(slot.as_mut_ptr(), args);
+            pin::Pin::new_unchecked(slot.assume_init_mut())
+        }
+    }
+
+    /// Build the named future directly on the heap
+    ///
+    /// Unlike <code>[Box::pin]\(Self::new(args)\)</code>, the future is not moved after it was
+    /// created. With optimizations, it is usually created on the heap directly, but this is not
+    /// guaranteed.
+    ///
+    /// [Box::pin]: alloc::boxed::Box::pin
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    fn boxed_pin(args: Self::Args) -> pin::Pin<alloc::boxed::Box<Self>>
+    where
+        Self: Sized,
+    {
+        use alloc::alloc::{Layout, alloc, handle_alloc_error};
+        use alloc::boxed::Box;
+
+        let layout = Layout::new::<Self>();
+        let slot = if layout.size() == 0 {
+            ptr::NonNull::<Self>::dangling().as_ptr()
+        } else {
+            let slot = unsafe { alloc(layout) }.cast::<Self>();
+            if slot.is_null() {
+                handle_alloc_error(layout);
+            }
+            slot
+        };
+        unsafe {
+            Self::init_raw(slot, args);
+            Box::into_pin(Box::from_raw(slot))
+        }
+    }
+}
+
+/// A named future that is stored in a user-declared struct
+///
+/// # Safety
+///
+/// `Bytes` must be `Bytes<{ Self::SIZE_OF }, { Self::ALIGN_OF }>`.
+pub unsafe trait Declared: NamedFuture {
+    /// The storage of the unnamed future
+    type Bytes;
+}
+
+/// A user-declared `struct Name(Storage<Self>);`, implemented by `#[derive(NamedFuture)]`
+///
+/// # Safety
+///
+/// `Self` must be a tuple struct with the single field [`Storage<Self>`](crate::Storage).
+pub unsafe trait DeclaredStruct: Declared + Sized {
+    /// Wrap the storage
+    fn from_storage(storage: crate::Storage<Self>) -> Self;
+
+    /// Access the storage
+    fn storage(&mut self) -> &mut crate::Storage<Self>;
+
+    /// Access the storage of a possibly uninitialized struct
+    ///
+    /// # Safety
+    ///
+    /// `this` must point to an allocation that is big enough for `Self`.
+    unsafe fn storage_raw(this: *mut Self) -> *mut crate::Storage<Self>;
 }
 
+/// A required argument of a <code>#\[named_future(builder)\]</code> that was not set yet
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unset;
+
+/// A required argument of a <code>#\[named_future(builder)\]</code> that was set
+#[derive(Debug, Clone, Copy)]
+pub struct Set<T>(pub T);
+
+/// The output of a named future that can be retried or served, i.e. a [`Result`]
+pub trait IsResult {
+    /// The success type
+    type Ok;
+    /// The error type
+    type Err;
+
+    /// Convert `self` into a [`Result`]
+    fn into_result(self) -> Result<Self::Ok, Self::Err>;
+}
+
+impl<T, E> IsResult for Result<T, E> {
+    type Err = E;
+    type Ok = T;
+
+    #[inline(always)]
+    fn into_result(self) -> Result<T, E> {
+        self
+    }
+}
+
+/// The error type of the named future `F`
+pub type RetryError<F> = <<F as future::Future>::Output as IsResult>::Err;
+
 /// An array `[MaybeUninit<u8>; SIZE_OF]` with an alignment of (at least) `ALIGN_OF`
 pub type Bytes<const SIZE_OF: usize, const ALIGN_OF: usize> =
     <align::Int<ALIGN_OF> as align::Aligner>::Aligned<SIZE_OF>;
@@ -80,32 +236,528 @@ where
 {
 }
 
+/// The storage of an unnamed future, and the state of the runtime checks of the `checked` mode
+///
+/// The slot is `!Unpin`, so that references to it do not assert uniqueness, which would
+/// invalidate the self-references of the unnamed future.
+#[repr(C)]
+pub struct Slot<F> {
+    future: mem::MaybeUninit<F>,
+    status: Status,
+    checks: checks::Checks,
+    _pin: marker::PhantomPinned,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    /// The arguments are stored outside of the slot, and the future was not created yet
+    NotStarted,
+    /// The future can be polled
+    Running,
+    /// The future is being polled, or `poll()` panicked
+    Polling,
+    /// `poll()` panicked, and the future was dropped already
+    Empty,
+    /// The future completed
+    Finished,
+    /// The future was aborted, and dropped already
+    #[cfg(target_has_atomic = "ptr")]
+    Aborted,
+    /// `poll()` panicked, the panic was caught, and the future was dropped already
+    #[cfg(feature = "std")]
+    Caught,
+}
+
+impl Status {
+    /// `true` if the unnamed future was not dropped yet
+    #[inline(always)]
+    fn has_future(self) -> bool {
+        match self {
+            Status::NotStarted | Status::Empty => false,
+            #[cfg(target_has_atomic = "ptr")]
+            Status::Aborted => false,
+            #[cfg(feature = "std")]
+            Status::Caught => false,
+            Status::Running | Status::Polling | Status::Finished => true,
+        }
+    }
+}
+
+impl<F> Slot<F> {
+    /// Wrap a future that is stored as its real type
+    #[inline(always)]
+    pub fn new(name: &'static str, future: F) -> Self {
+        Self {
+            future: mem::MaybeUninit::new(future),
+            status: Status::Running,
+            checks: checks::Checks::new(name),
+            _pin: marker::PhantomPinned,
+        }
+    }
+
+    /// A slot whose future is created when it is polled for the first time
+    ///
+    /// Used to implement `#[named_future(Clone)]`.
+    #[inline(always)]
+    pub fn not_started(name: &'static str) -> Self {
+        Self {
+            future: mem::MaybeUninit::uninit(),
+            status: Status::NotStarted,
+            checks: checks::Checks::new(name),
+            _pin: marker::PhantomPinned,
+        }
+    }
+}
+
+impl<F> fmt::Debug for Slot<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Slot").finish_non_exhaustive()
+    }
+}
+
+impl<F> Drop for Slot<F> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        if self.status.has_future() {
+            self.status = Status::Empty;
+            // SAFETY: `Bytes` have no drop glue, and opaque futures are dropped in place.
+            unsafe { self.future.assume_init_drop() };
+        }
+        self.checks.dropped();
+    }
+}
+
+/// Poll the future in `this`, and remember if `poll()` panicked
+#[inline(always)]
+unsafe fn poll_slot<F, Fut: future::Future>(
+    name: &'static str,
+    this: pin::Pin<&mut Slot<F>>,
+    cx: &mut task::Context<'_>,
+) -> task::Poll<Fut::Output> {
+    let this: *mut Slot<F> = this.get_unchecked_mut();
+    let _guard = (*ptr::addr_of_mut!((*this).checks)).enter();
+    let status = ptr::addr_of_mut!((*this).status);
+    match *status {
+        Status::NotStarted => unreachable!("`{name}` was polled before it was started"),
+        Status::Running => {},
+        Status::Polling | Status::Empty => panic!("`{name}` was polled after it panicked"),
+        Status::Finished => panic!("`{name}` was polled after it completed"),
+        #[cfg(target_has_atomic = "ptr")]
+        Status::Aborted => panic!("`{name}` was polled after it completed"),
+        #[cfg(feature = "std")]
+        Status::Caught => panic!("`{name}` was polled after it completed"),
+    }
+    *status = Status::Polling;
+    // Derive the pointer from `this`, so it is valid for the whole slot, and do not create
+    // a reference to the `Unpin` bytes.
+    let future = ptr::addr_of_mut!((*this).future).cast::<Fut>();
+    let result = pin::Pin::new_unchecked(&mut *future).poll(cx);
+    *status = match result {
+        task::Poll::Ready(_) => Status::Finished,
+        task::Poll::Pending => Status::Running,
+    };
+    result
+}
+
+/// Like [`poll_slot()`], but catch a panic, and drop the future immediately
+#[cfg(feature = "std")]
+#[inline(always)]
+unsafe fn poll_slot_catch_unwind<F, Fut: future::Future>(
+    name: &'static str,
+    mut this: pin::Pin<&mut Slot<F>>,
+    cx: &mut task::Context<'_>,
+) -> task::Poll<Result<Fut::Output, crate::PanicPayload>> {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    // Checked outside of `catch_unwind()`, so that the future is not dropped twice.
+    match this.status {
+        Status::NotStarted => unreachable!("`{name}` was polled before it was started"),
+        Status::Running => {},
+        Status::Polling | Status::Empty => panic!("`{name}` was polled after it panicked"),
+        Status::Finished => panic!("`{name}` was polled after it completed"),
+        #[cfg(target_has_atomic = "ptr")]
+        Status::Aborted => panic!("`{name}` was polled after it completed"),
+        #[cfg(feature = "std")]
+        Status::Caught => panic!("`{name}` was polled after it completed"),
+    }
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        poll_slot::<F, Fut>(name, this.as_mut(), cx)
+    }));
+    match result {
+        Ok(poll) => poll.map(Ok),
+        Err(payload) => {
+            let this: *mut Slot<F> = this.get_unchecked_mut();
+            *ptr::addr_of_mut!((*this).status) = Status::Caught;
+            ptr::drop_in_place(ptr::addr_of_mut!((*this).future).cast::<Fut>());
+            task::Poll::Ready(Err(payload))
+        },
+    }
+}
+
+/// Poll the future in `this` with `poll`, unless it was aborted
+///
+/// An aborted future is dropped in place, and the slot behaves as if the future completed.
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+unsafe fn poll_slot_abortable<F, Fut, T>(
+    abort: &AbortRegistration,
+    mut this: pin::Pin<&mut Slot<F>>,
+    cx: &mut task::Context<'_>,
+    poll: impl FnOnce(pin::Pin<&mut Slot<F>>, &mut task::Context<'_>) -> task::Poll<T>,
+) -> task::Poll<Result<T, crate::Aborted>> {
+    if this.status == Status::Running && abort.is_aborted() {
+        return abort_slot::<F, Fut, T>(this);
+    }
+    if let task::Poll::Ready(output) = poll(this.as_mut(), cx) {
+        return task::Poll::Ready(Ok(output));
+    }
+    // Check again after the registration, so an abort in between is not missed.
+    abort.register(cx);
+    if this.status == Status::Running && abort.is_aborted() {
+        return abort_slot::<F, Fut, T>(this);
+    }
+    task::Poll::Pending
+}
+
+#[cfg(target_has_atomic = "ptr")]
+#[cold]
+unsafe fn abort_slot<F, Fut, T>(
+    this: pin::Pin<&mut Slot<F>>,
+) -> task::Poll<Result<T, crate::Aborted>> {
+    let this: *mut Slot<F> = this.get_unchecked_mut();
+    let _guard = (*ptr::addr_of_mut!((*this).checks)).enter();
+    *ptr::addr_of_mut!((*this).status) = Status::Aborted;
+    ptr::drop_in_place(ptr::addr_of_mut!((*this).future).cast::<Fut>());
+    task::Poll::Ready(Err(crate::Aborted))
+}
+
+/// `true` if the future was not polled to completion yet, and did not panic
+///
+/// Used to implement `#[named_future(on_cancel = …)]`.
+#[inline(always)]
+#[must_use]
+pub fn is_pending<F>(this: &Slot<F>) -> bool {
+    matches!(this.status, Status::NotStarted | Status::Running)
+}
+
+/// Create the future of a slot that was created by [`Slot::not_started()`]
+#[inline(always)]
+pub fn start_opaque<F>(this: &mut Slot<F>, future: F) {
+    debug_assert!(this.status == Status::NotStarted);
+    this.future.write(future);
+    this.status = Status::Running;
+}
+
+/// [`poll()`](future::Future::poll) for a named future that is stored as its real type
+#[inline(always)]
+pub fn poll_opaque<F: future::Future>(
+    name: &'static str,
+    this: pin::Pin<&mut Slot<F>>,
+    cx: &mut task::Context<'_>,
+) -> task::Poll<F::Output> {
+    unsafe { poll_slot::<F, F>(name, this, cx) }
+}
+
+/// [`poll()`](future::Future::poll) for a named future that is stored as its real type,
+/// catching panics
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[inline(always)]
+pub fn poll_opaque_catch_unwind<F: future::Future>(
+    name: &'static str,
+    this: pin::Pin<&mut Slot<F>>,
+    cx: &mut task::Context<'_>,
+) -> task::Poll<Result<F::Output, crate::PanicPayload>> {
+    unsafe { poll_slot_catch_unwind::<F, F>(name, this, cx) }
+}
+
+/// [`poll()`](future::Future::poll) for an abortable named future that is stored as its real
+/// type
+///
+/// `poll` is [`poll_opaque()`] or [`poll_opaque_catch_unwind()`].
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+pub fn poll_opaque_abortable<F: future::Future, T>(
+    abort: &AbortRegistration,
+    this: pin::Pin<&mut Slot<F>>,
+    cx: &mut task::Context<'_>,
+    poll: impl FnOnce(pin::Pin<&mut Slot<F>>, &mut task::Context<'_>) -> task::Poll<T>,
+) -> task::Poll<Result<T, crate::Aborted>> {
+    unsafe { poll_slot_abortable::<F, F, T>(abort, this, cx, poll) }
+}
+
+/// [`NamedFuture::new()`] for a named future
+///
+/// SAFETY: `Generator` must be the generator of the named future that stores its future in `B`.
+#[inline(always)]
+pub unsafe fn new<Generator, Args, Fut, B>(
+    name: &'static str,
+    generator: &Generator,
+    args: Args,
+) -> Slot<B>
+where
+    Generator: Fn(Args) -> Fut,
+{
+    checks::layout::<Fut, B>(name);
+    // Never create a reference to the uninitialized bytes.
+    let mut future = mem::MaybeUninit::<B>::uninit();
+    future.as_mut_ptr().cast::<Fut>().write(generator(args));
+    Slot::new(name, future.assume_init())
+}
+
+/// [`NamedFuture::init_raw()`] for a named future
+///
+/// SAFETY: `Generator` must be the generator of the named future that stores its future in `B`,
+/// and `this` must be valid for writes.
+#[inline(always)]
+pub unsafe fn init<Generator, Args, Fut, B>(
+    name: &'static str,
+    generator: &Generator,
+    this: *mut Slot<B>,
+    args: Args,
+) where
+    Generator: Fn(Args) -> Fut,
+{
+    checks::layout::<Fut, B>(name);
+    ptr::addr_of_mut!((*this).status).write(Status::Running);
+    ptr::addr_of_mut!((*this).checks).write(checks::Checks::new(name));
+    ptr::addr_of_mut!((*this).future)
+        .cast::<Fut>()
+        .write(generator(args));
+}
+
+/// Create the future of a slot that was created by [`Slot::not_started()`]
+///
+/// SAFETY: `Generator` must be the generator of the named future that stores its future in `B`.
+#[inline(always)]
+pub unsafe fn start<Generator, Args, Fut, B>(
+    name: &'static str,
+    generator: &Generator,
+    this: &mut Slot<B>,
+    args: Args,
+) where
+    Generator: Fn(Args) -> Fut,
+{
+    checks::layout::<Fut, B>(name);
+    debug_assert!(this.status == Status::NotStarted);
+    // Never create a reference to the uninitialized bytes.
+    let slot: *mut Slot<B> = this;
+    ptr::addr_of_mut!((*slot).future)
+        .cast::<Fut>()
+        .write(generator(args));
+    (*slot).status = Status::Running;
+}
+
 /// [`poll()`](future::Future::poll) for a named future
 ///
-/// SAFETY: `Generator` must be the generator of `This`.
+/// SAFETY: `Generator` must be the generator of the named future that stores its future in `B`.
 #[inline(always)]
-pub unsafe fn poll<Generator, Args, Fut, This>(
+pub unsafe fn poll<Generator, Args, Fut, B>(
+    name: &'static str,
     _: &Generator,
-    this: pin::Pin<&mut This>,
+    this: pin::Pin<&mut Slot<B>>,
     cx: &mut task::Context<'_>,
 ) -> task::Poll<Fut::Output>
 where
     Generator: Fn(Args) -> Fut,
     Fut: future::Future,
 {
-    let fut: pin::Pin<&mut Fut> = mem::transmute(this);
-    fut.poll(cx)
+    poll_slot::<B, Fut>(name, this, cx)
+}
+
+/// [`poll()`](future::Future::poll) for a named future, catching panics
+///
+/// SAFETY: `Generator` must be the generator of the named future that stores its future in `B`.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[inline(always)]
+pub unsafe fn poll_catch_unwind<Generator, Args, Fut, B>(
+    name: &'static str,
+    _: &Generator,
+    this: pin::Pin<&mut Slot<B>>,
+    cx: &mut task::Context<'_>,
+) -> task::Poll<Result<Fut::Output, crate::PanicPayload>>
+where
+    Generator: Fn(Args) -> Fut,
+    Fut: future::Future,
+{
+    poll_slot_catch_unwind::<B, Fut>(name, this, cx)
+}
+
+/// [`poll()`](future::Future::poll) for an abortable named future
+///
+/// `poll` is [`poll()`] or [`poll_catch_unwind()`].
+///
+/// SAFETY: `Generator` must be the generator of the named future that stores its future in `B`.
+#[cfg(target_has_atomic = "ptr")]
+#[inline(always)]
+pub unsafe fn poll_abortable<Generator, Args, Fut, B, T>(
+    _: &Generator,
+    abort: &AbortRegistration,
+    this: pin::Pin<&mut Slot<B>>,
+    cx: &mut task::Context<'_>,
+    poll: impl FnOnce(pin::Pin<&mut Slot<B>>, &mut task::Context<'_>) -> task::Poll<T>,
+) -> task::Poll<Result<T, crate::Aborted>>
+where
+    Generator: Fn(Args) -> Fut,
+    Fut: future::Future,
+{
+    poll_slot_abortable::<B, Fut, T>(abort, this, cx, poll)
 }
 
 /// [`ptr::drop_in_place`] for a named future
 ///
-/// SAFETY: `Generator` must be the generator of `This`.
+/// SAFETY: `Generator` must be the generator of the named future that stores its future in `B`.
 #[inline(always)]
-pub unsafe fn drop<Generator, Args, Fut, This>(_: &Generator, this: &mut This)
+pub unsafe fn drop<Generator, Args, Fut, B>(_: &Generator, this: &mut Slot<B>)
 where
     Generator: Fn(Args) -> Fut,
     Fut: future::Future,
 {
-    let fut: &mut Fut = mem::transmute(this);
-    ptr::drop_in_place(fut);
+    this.checks.dropping();
+    if this.status.has_future() {
+        this.status = Status::Empty;
+        let this: *mut Slot<B> = this;
+        ptr::drop_in_place(ptr::addr_of_mut!((*this).future).cast::<Fut>());
+    }
+}
+
+/// Wrap the slot of a user-declared struct
+#[inline(always)]
+pub fn storage<T: Declared>(slot: Slot<T::Bytes>) -> crate::Storage<T> {
+    crate::Storage {
+        _slot: slot,
+        _not_send_or_sync: marker::PhantomData,
+        _pin: marker::PhantomPinned,
+    }
+}
+
+/// Access the slot of a user-declared struct
+#[inline(always)]
+pub fn slot<T: Declared>(storage: &mut crate::Storage<T>) -> &mut Slot<T::Bytes> {
+    &mut storage._slot
+}
+
+/// Access the slot of a possibly uninitialized user-declared struct
+///
+/// SAFETY: `storage` must point to an allocation that is big enough for `Storage<T>`.
+#[inline(always)]
+pub unsafe fn slot_raw<T: Declared>(storage: *mut crate::Storage<T>) -> *mut Slot<T::Bytes> {
+    ptr::addr_of_mut!((*storage)._slot)
+}
+
+#[cfg(any(feature = "checked", debug_assertions))]
+mod checks {
+    use core::mem;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum State {
+        Idle,
+        Polling,
+        Dropped,
+    }
+
+    pub(super) struct Checks {
+        name: &'static str,
+        /// Address of `self` in the first call to `poll()`, or `0`
+        address: usize,
+        state: State,
+    }
+
+    /// Resets the state after `poll()` returned, or if it panicked
+    pub(super) struct Guard<'a>(&'a mut State);
+
+    impl Drop for Guard<'_> {
+        #[inline]
+        fn drop(&mut self) {
+            *self.0 = State::Idle;
+        }
+    }
+
+    #[track_caller]
+    pub(super) fn layout<Fut, B>(name: &'static str) {
+        assert!(
+            mem::size_of::<B>() == mem::size_of::<Fut>(),
+            "`{name}` stores its future in {} bytes, but the future needs {} bytes",
+            mem::size_of::<B>(),
+            mem::size_of::<Fut>(),
+        );
+        assert!(
+            mem::align_of::<B>() == mem::align_of::<Fut>(),
+            "`{name}` stores its future with an alignment of {} bytes, but the future needs {}",
+            mem::align_of::<B>(),
+            mem::align_of::<Fut>(),
+        );
+    }
+
+    impl Checks {
+        #[inline]
+        pub(super) fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                address: 0,
+                state: State::Idle,
+            }
+        }
+
+        #[track_caller]
+        pub(super) fn enter(&mut self) -> Guard<'_> {
+            let name = self.name;
+            let address = self as *const Self as usize;
+            match self.state {
+                State::Idle => {},
+                State::Polling => panic!("`{name}` was polled while it was already being polled"),
+                State::Dropped => panic!("`{name}` was polled after it was dropped"),
+            }
+            if self.address == 0 {
+                self.address = address;
+            } else {
+                assert!(
+                    self.address == address,
+                    "`{name}` was moved after it was polled for the first time",
+                );
+            }
+            self.state = State::Polling;
+            Guard(&mut self.state)
+        }
+
+        #[track_caller]
+        pub(super) fn dropping(&mut self) {
+            let name = self.name;
+            assert!(self.state != State::Dropped, "`{name}` was dropped twice");
+        }
+
+        #[inline]
+        pub(super) fn dropped(&mut self) {
+            self.state = State::Dropped;
+        }
+    }
+}
+
+#[cfg(not(any(feature = "checked", debug_assertions)))]
+mod checks {
+    pub(super) struct Checks;
+
+    pub(super) struct Guard;
+
+    #[inline(always)]
+    pub(super) fn layout<Fut, B>(_: &'static str) {}
+
+    impl Checks {
+        #[inline(always)]
+        pub(super) fn new(_: &'static str) -> Self {
+            Self
+        }
+
+        #[inline(always)]
+        pub(super) fn enter(&mut self) -> Guard {
+            Guard
+        }
+
+        #[inline(always)]
+        pub(super) fn dropping(&mut self) {}
+
+        #[inline(always)]
+        pub(super) fn dropped(&mut self) {}
+    }
 }
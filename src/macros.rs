@@ -0,0 +1,332 @@
+/// Give your future a name without using the proc macro
+///
+/// This macro does the same as the attribute [`#[named_future]`](crate),
+/// but it is implemented with `macro_rules!`, so it can be used if the feature `proc-macros`
+/// is disabled.
+///
+/// ```rust
+/// # use named_future::named_future_fn;
+/// named_future_fn! {
+///     /// A slow multiplication
+///     pub async fn slow_mul(factor1: u32, factor2: u32) -> u32
+///     as
+///     /// Future generated by [`slow_mul`]
+///     pub SlowMul: Send + Sync
+///     {
+/// #       /*
+///         sleep(Duration::from_secs(5)).await;
+/// #       */
+///         factor1 * factor2
+///     }
+/// }
+///
+/// let future: SlowMul = slow_mul(6, 7);
+/// ```
+///
+/// The name of the generated `struct`, and optionally its visibility and documentation,
+/// follow after the keyword `as`.
+/// The `struct` can be made `Send` and `Sync` by appending `: Send`, `: Sync`, or `: Send + Sync`.
+///
+/// The function may have lifetime parameters without bounds, e.g.
+/// `async fn read<'a>(value: &'a u32) -> u32`.
+/// The arguments must be plain identifiers.
+#[macro_export]
+macro_rules! named_future_fn {
+    (
+        $(#[$fn_meta:meta])*
+        $fn_vis:vis async fn $fn_name:ident $(<$($lt:lifetime),+ $(,)?>)?
+        ($($arg:ident: $arg_ty:ty),* $(,)?) $(-> $ret:ty)?
+        as
+        $(#[$struct_meta:meta])*
+        $struct_vis:vis $struct_name:ident $(: $auto:ident $(+ $autos:ident)*)?
+        $body:block
+    ) => {
+        $crate::named_future_fn! {
+            @statics [] [$($($lt),+)?]
+            [$(#[$fn_meta])*] [$fn_vis] $fn_name [$($($lt),+)?]
+            [$($arg: $arg_ty),*] [$crate::named_future_fn!(@ret $($ret)?)]
+            [$(#[$struct_meta])*] [$struct_vis] $struct_name [$($auto $($autos)*)?]
+            $body
+        }
+    };
+
+    // Replace every lifetime with `'static`
+    (@statics [$($static:tt)*] [] $($rest:tt)*) => {
+        $crate::named_future_fn! { @expand [$($static)*] $($rest)* }
+    };
+    (@statics [$($static:tt)*] [$lt:lifetime $(, $lts:lifetime)*] $($rest:tt)*) => {
+        $crate::named_future_fn! { @statics [$($static)* 'static,] [$($lts),*] $($rest)* }
+    };
+
+    (@ret) => { () };
+    (@ret $ret:ty) => { $ret };
+
+    (
+        @expand [$($static:tt)*]
+        [$(#[$fn_meta:meta])*] [$fn_vis:vis] $fn_name:ident [$($lt:lifetime),*]
+        [$($arg:ident: $arg_ty:ty),*] [$ret:ty]
+        [$(#[$struct_meta:meta])*] [$struct_vis:vis] $struct_name:ident [$($auto:ident)*]
+        $body:block
+    ) => {
+        $(#[$fn_meta])*
+        #[inline]
+        #[allow(clippy::needless_lifetimes)]
+        $fn_vis fn $fn_name<$($lt),*>($($arg: $arg_ty),*) -> $struct_name<$($lt),*> {
+            <$struct_name<$($lt),*> as $crate::machinery::NamedFuture>::new(($($arg,)*))
+        }
+
+        $(#[$struct_meta])*
+        #[repr(transparent)]
+        #[must_use = "futures do nothing unless you `.await` or poll them"]
+        #[allow(clippy::type_complexity)]
+        $struct_vis struct $struct_name<$($lt),*> {
+            _future: $crate::machinery::Slot<$crate::machinery::Bytes<
+                { <$struct_name<$($static)*> as $crate::machinery::NamedFuture>::SIZE_OF },
+                { <$struct_name<$($static)*> as $crate::machinery::NamedFuture>::ALIGN_OF },
+            >>,
+            _not_send_or_sync: ::core::marker::PhantomData<*mut ()>,
+            _pin: ::core::marker::PhantomPinned,
+            // The future owns its arguments and produces its output, so it has the same variance.
+            _phantom: ::core::marker::PhantomData<(($($arg_ty,)*), fn() -> $ret, $(&$lt (),)*)>,
+        }
+
+        #[allow(clippy::needless_lifetimes)]
+        const _: () = {
+            #[inline(always)]
+            async fn __named_future_implementation<$($lt),*>($($arg: $arg_ty),*) -> $ret $body
+
+            const _: () = {
+                async fn $fn_name<$($lt),*>(($($arg,)*): ($($arg_ty,)*)) -> $ret {
+                    __named_future_implementation($($arg),*).await
+                }
+
+                impl<$($lt),*> $crate::machinery::NamedFuture for $struct_name<$($lt),*> {
+                    const NAME: &'static ::core::primitive::str = ::core::stringify!($struct_name);
+                    const ALIGN_OF: ::core::primitive::usize =
+                        $crate::machinery::align_of(&$fn_name);
+                    const SIZE_OF: ::core::primitive::usize =
+                        $crate::machinery::size_of(&$fn_name);
+
+                    type Args = ($($arg_ty,)*);
+
+                    #[inline(always)]
+                    fn new(args: Self::Args) -> Self {
+                        let name = ::core::stringify!($struct_name);
+                        Self {
+                            _future: unsafe { $crate::machinery::new(name, &$fn_name, args) },
+                            _not_send_or_sync: ::core::marker::PhantomData,
+                            _pin: ::core::marker::PhantomPinned,
+                            _phantom: ::core::marker::PhantomData,
+                        }
+                    }
+
+                    #[inline(always)]
+                    unsafe fn init_raw(slot: *mut Self, args: Self::Args) {
+                        let name = ::core::stringify!($struct_name);
+                        // All other fields are zero-sized.
+                        let slot = unsafe { ::core::ptr::addr_of_mut!((*slot)._future) };
+                        unsafe { $crate::machinery::init(name, &$fn_name, slot, args) };
+                    }
+                }
+
+                impl<$($lt),*> ::core::ops::Drop for $struct_name<$($lt),*> {
+                    #[inline]
+                    fn drop(&mut self) {
+                        unsafe { $crate::machinery::drop(&$fn_name, &mut self._future) };
+                    }
+                }
+
+                impl<$($lt),*> ::core::future::Future for $struct_name<$($lt),*> {
+                    type Output = $ret;
+
+                    #[inline]
+                    fn poll(
+                        self: ::core::pin::Pin<&mut Self>,
+                        cx: &mut ::core::task::Context<'_>,
+                    ) -> ::core::task::Poll<Self::Output> {
+                        let fut = unsafe { self.map_unchecked_mut(|this| &mut this._future) };
+                        let name = ::core::stringify!($struct_name);
+                        unsafe { $crate::machinery::poll(name, &$fn_name, fut, cx) }
+                    }
+                }
+
+                impl<$($lt),*> ::core::fmt::Debug for $struct_name<$($lt),*> {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                        f.debug_struct(::core::stringify!($struct_name)).finish_non_exhaustive()
+                    }
+                }
+
+                $crate::named_future_fn! { @auto [$($auto)*] $fn_name $struct_name [$($lt),*] }
+            };
+        };
+    };
+
+    // Implement the requested auto traits one by one
+    (@auto [] $($rest:tt)*) => {};
+    (@auto [Send $($autos:ident)*] $fn_name:ident $struct_name:ident [$($lt:lifetime),*]) => {
+        const _: () = $crate::machinery::ensure_send(&$fn_name);
+
+        unsafe impl<$($lt),*> ::core::marker::Send for $struct_name<$($lt),*> {}
+
+        $crate::named_future_fn! { @auto [$($autos)*] $fn_name $struct_name [$($lt),*] }
+    };
+    (@auto [Sync $($autos:ident)*] $fn_name:ident $struct_name:ident [$($lt:lifetime),*]) => {
+        const _: () = $crate::machinery::ensure_sync(&$fn_name);
+
+        unsafe impl<$($lt),*> ::core::marker::Sync for $struct_name<$($lt),*> {}
+
+        $crate::named_future_fn! { @auto [$($autos)*] $fn_name $struct_name [$($lt),*] }
+    };
+    (@auto [$auto:ident $($autos:ident)*] $($rest:tt)*) => {
+        ::core::compile_error!(::core::concat!(
+            "expected `Send` or `Sync`, found `", ::core::stringify!($auto), "`",
+        ));
+    };
+}
+
+/// Declare an `enum` of unstarted named futures that can be serialized and deserialized
+///
+/// Every variant holds a [`Lazy`](crate::Lazy) of a named future. [`Lazy`](crate::Lazy) is
+/// serialized as an envelope `{ name, args }`, which contains
+/// <code>[NamedFuture::NAME]</code> and the argument tuple.
+/// The `enum` uses the name to deserialize the envelope into the right variant.
+///
+/// ```rust
+/// # #![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+/// use named_future::{Lazy, named_future, named_future_registry};
+///
+/// #[named_future]
+/// async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+///     factor1 * factor2
+/// }
+///
+/// #[named_future]
+/// async fn greet(name: String) -> String {
+///     format!("Hello, {name}!")
+/// }
+///
+/// named_future_registry! {
+///     /// A queued job
+///     pub enum Job {
+///         SlowMul(SlowMul),
+///         Greet(Greet),
+///     }
+/// }
+///
+/// # fn main() {
+/// let job = Job::from(Lazy::<SlowMul>::new((6, 7)));
+/// assert_eq!(job.name(), "SlowMul");
+/// # }
+/// ```
+///
+/// Every named future in the `enum` must have a different name, and `'static` arguments that
+/// implement `Serialize` and `Deserialize`.
+/// Serializing a future that was polled already fails.
+///
+/// [NamedFuture::NAME]: crate::NamedFuture::NAME
+#[cfg(feature = "serde")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde")))]
+#[macro_export]
+macro_rules! named_future_registry {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident($ty:ty)),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($(#[$variant_meta])* $variant($crate::Lazy<$ty>),)*
+        }
+
+        impl $name {
+            /// The name of the named future
+            #[allow(dead_code)]
+            $vis fn name(&self) -> &'static ::core::primitive::str {
+                match *self {
+                    $(Self::$variant(_) => <$ty as $crate::NamedFuture>::NAME,)*
+                }
+            }
+        }
+
+        $(
+            impl ::core::convert::From<$crate::Lazy<$ty>> for $name {
+                #[inline]
+                fn from(future: $crate::Lazy<$ty>) -> Self {
+                    Self::$variant(future)
+                }
+            }
+        )*
+
+        impl $crate::machinery::serde::Serialize for $name {
+            fn serialize<S: $crate::machinery::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                match *self {
+                    $(
+                        Self::$variant(ref future) => {
+                            $crate::machinery::serde::Serialize::serialize(future, serializer)
+                        },
+                    )*
+                }
+            }
+        }
+
+        impl<'de> $crate::machinery::serde::Deserialize<'de> for $name {
+            #[inline]
+            fn deserialize<D: $crate::machinery::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                $crate::machinery::deserialize_registry(deserializer)
+            }
+        }
+
+        impl<'de> $crate::machinery::Registry<'de> for $name {
+            const NAMES: &'static [&'static ::core::primitive::str] =
+                &[$(<$ty as $crate::NamedFuture>::NAME),*];
+
+            fn deserialize_args<A: $crate::machinery::ArgsAccess<'de>>(
+                name: &'static ::core::primitive::str,
+                access: A,
+            ) -> ::core::result::Result<Self, A::Error> {
+                $(
+                    if name == <$ty as $crate::NamedFuture>::NAME {
+                        return access.args().map(|args| Self::$variant($crate::Lazy::new(args)));
+                    }
+                )*
+                ::core::unreachable!("unknown named future `{}`", name)
+            }
+        }
+
+        impl ::core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                match *self {
+                    $(Self::$variant(ref future) => f.debug_tuple(::core::stringify!($variant)).field(future).finish(),)*
+                }
+            }
+        }
+    };
+}
+
+/// Expand the tokens if the feature `alloc` is enabled
+///
+/// Generated code cannot test the features of `named-future` with `#[cfg]`.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __named_future_if_alloc {
+    ($($tt:tt)*) => {
+        $($tt)*
+    };
+}
+
+/// Expand the tokens if the feature `alloc` is enabled
+///
+/// Generated code cannot test the features of `named-future` with `#[cfg]`.
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __named_future_if_alloc {
+    ($($tt:tt)*) => {};
+}
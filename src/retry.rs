@@ -0,0 +1,245 @@
+use core::time::Duration;
+use core::{fmt, future, mem, pin, ptr, task};
+
+use crate::machinery::IsResult;
+use crate::{NamedFuture, Timer};
+
+/// What to do after an attempt of a [`Retry`] failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision<B> {
+    /// Give up, and return the error
+    Stop,
+    /// Start the next attempt immediately
+    Now,
+    /// Start the next attempt when the backoff future completed
+    After(B),
+}
+
+/// Decides if a failed attempt of a [`Retry`] is repeated
+///
+/// `E` is the error type of the named future.
+/// A closure `FnMut(attempt: u32, error: &E) -> bool` retries immediately if it returns `true`.
+pub trait RetryPolicy<E> {
+    /// A future that delays the next attempt
+    type Backoff: future::Future<Output = ()>;
+
+    /// Decide what to do after the `attempt`-th attempt failed with `error`
+    ///
+    /// The first attempt is `1`.
+    fn retry(&mut self, attempt: u32, error: &E) -> RetryDecision<Self::Backoff>;
+}
+
+impl<E, F: FnMut(u32, &E) -> bool> RetryPolicy<E> for F {
+    type Backoff = future::Ready<()>;
+
+    #[inline]
+    fn retry(&mut self, attempt: u32, error: &E) -> RetryDecision<Self::Backoff> {
+        if self(attempt, error) {
+            RetryDecision::Now
+        } else {
+            RetryDecision::Stop
+        }
+    }
+}
+
+/// Make at most `N` attempts, without a delay between them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attempts(pub u32);
+
+impl<E> RetryPolicy<E> for Attempts {
+    type Backoff = future::Ready<()>;
+
+    #[inline]
+    fn retry(&mut self, attempt: u32, _: &E) -> RetryDecision<Self::Backoff> {
+        if attempt < self.0 {
+            RetryDecision::Now
+        } else {
+            RetryDecision::Stop
+        }
+    }
+}
+
+/// Make at most `max_attempts` attempts, and double the delay after each failed attempt
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialBackoff<T> {
+    timer: T,
+    max_attempts: u32,
+    delay: Duration,
+    max_delay: Duration,
+}
+
+impl<T: Timer> ExponentialBackoff<T> {
+    /// Wait `initial_delay` after the first failed attempt, and twice as long after each
+    /// following failed attempt
+    #[must_use]
+    pub fn new(timer: T, max_attempts: u32, initial_delay: Duration) -> Self {
+        Self {
+            timer,
+            max_attempts,
+            delay: initial_delay,
+            max_delay: Duration::MAX,
+        }
+    }
+
+    /// Never wait longer than `max_delay`
+    #[must_use]
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self.delay = self.delay.min(max_delay);
+        self
+    }
+}
+
+impl<T: Timer, E> RetryPolicy<E> for ExponentialBackoff<T> {
+    type Backoff = T::Sleep;
+
+    fn retry(&mut self, attempt: u32, _: &E) -> RetryDecision<Self::Backoff> {
+        if attempt >= self.max_attempts {
+            return RetryDecision::Stop;
+        }
+        let delay = self.delay;
+        self.delay = delay.saturating_mul(2).min(self.max_delay);
+        RetryDecision::After(self.timer.sleep(delay))
+    }
+}
+
+/// A named future that is recreated from its arguments after it returned an `Err`
+///
+/// The named future must return a [`Result`], and its arguments must be [`Clone`].
+/// A [`RetryPolicy`] decides if a failed attempt is repeated, and how long to wait before.
+/// The named future of each attempt is created in place, when the attempt starts,
+/// and it is dropped as soon as it returned.
+///
+/// <code>#\[named_future(retry)\]</code> generates a type alias `SlowMulRetry<P>` for
+/// `Retry<SlowMul, P>`, and a function `slow_mul_retry(…, policy: P)` to create it.
+pub struct Retry<F, P>
+where
+    F: NamedFuture + future::Future,
+    F::Output: IsResult,
+    P: RetryPolicy<<F::Output as IsResult>::Err>,
+{
+    future: mem::MaybeUninit<F>,
+    backoff: Option<P::Backoff>,
+    state: State,
+    attempt: u32,
+    args: F::Args,
+    policy: P,
+    _pin: core::marker::PhantomPinned,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// `future` is uninitialized, and the next attempt starts after `backoff`, if any
+    Waiting,
+    /// `future` is initialized
+    Running,
+    /// The future returned
+    Finished,
+}
+
+impl<F, P> Retry<F, P>
+where
+    F: NamedFuture + future::Future,
+    F::Output: IsResult,
+    F::Args: Clone,
+    P: RetryPolicy<<F::Output as IsResult>::Err>,
+{
+    /// Store the arguments of the named future, and the policy
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub fn new(args: F::Args, policy: P) -> Self {
+        Self {
+            future: mem::MaybeUninit::uninit(),
+            backoff: None,
+            state: State::Waiting,
+            attempt: 0,
+            args,
+            policy,
+            _pin: core::marker::PhantomPinned,
+        }
+    }
+
+    /// The number of attempts that were started
+    #[inline]
+    #[must_use]
+    pub fn attempts(&self) -> u32 {
+        self.attempt
+    }
+}
+
+impl<F, P> fmt::Debug for Retry<F, P>
+where
+    F: NamedFuture + future::Future,
+    F::Output: IsResult,
+    P: RetryPolicy<<F::Output as IsResult>::Err>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retry")
+            .field("attempt", &self.attempt)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, P> future::Future for Retry<F, P>
+where
+    F: NamedFuture + future::Future,
+    F::Output: IsResult,
+    F::Args: Clone,
+    P: RetryPolicy<<F::Output as IsResult>::Err>,
+{
+    type Output = Result<<F::Output as IsResult>::Ok, <F::Output as IsResult>::Err>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // SAFETY: `future` and `backoff` are pinned, and never moved
+        let this = unsafe { self.get_unchecked_mut() };
+        loop {
+            match this.state {
+                State::Waiting => {
+                    if let Some(backoff) = &mut this.backoff {
+                        let backoff = unsafe { pin::Pin::new_unchecked(backoff) };
+                        if backoff.poll(cx).is_pending() {
+                            return task::Poll::Pending;
+                        }
+                        this.backoff = None;
+                    }
+                    unsafe { F::init_raw(this.future.as_mut_ptr(), this.args.clone()) };
+                    this.state = State::Running;
+                    this.attempt = this.attempt.saturating_add(1);
+                },
+                State::Running => {
+                    let future = unsafe { pin::Pin::new_unchecked(this.future.assume_init_mut()) };
+                    let output = match future.poll(cx) {
+                        task::Poll::Ready(output) => output.into_result(),
+                        task::Poll::Pending => return task::Poll::Pending,
+                    };
+                    this.state = State::Finished;
+                    unsafe { ptr::drop_in_place(this.future.as_mut_ptr()) };
+                    let error = match output {
+                        Ok(value) => return task::Poll::Ready(Ok(value)),
+                        Err(error) => error,
+                    };
+                    match this.policy.retry(this.attempt, &error) {
+                        RetryDecision::Stop => return task::Poll::Ready(Err(error)),
+                        RetryDecision::Now => {},
+                        RetryDecision::After(backoff) => this.backoff = Some(backoff),
+                    }
+                    this.state = State::Waiting;
+                },
+                State::Finished => panic!("`Retry` was polled after it completed"),
+            }
+        }
+    }
+}
+
+impl<F, P> Drop for Retry<F, P>
+where
+    F: NamedFuture + future::Future,
+    F::Output: IsResult,
+    P: RetryPolicy<<F::Output as IsResult>::Err>,
+{
+    fn drop(&mut self) {
+        if self.state == State::Running {
+            self.state = State::Finished;
+            unsafe { ptr::drop_in_place(self.future.as_mut_ptr()) };
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::{fmt, future, hint, mem, pin, task};
+
+/// A future that can be cloned, and whose output is delivered to all clones
+///
+/// The inner future is polled by one clone at a time, and only until it completes.
+/// Every clone then returns a clone of its output.
+/// Unlike a `Shared<Pin<Box<dyn Future>>>`, the type of the inner future stays nameable.
+///
+/// <code>#\[named_future(shared)\]</code> generates a type alias `SlowMulShared` for
+/// `Shared<SlowMul>`, and a function `slow_mul_shared(…)` to create it.
+///
+/// If the inner future panics, then the panic is propagated to the clone that polled it.
+/// The other clones are woken up, and poll the inner future again,
+/// so a named future will panic with a message like “`SlowMul` was polled after it panicked”.
+pub struct Shared<F: future::Future> {
+    inner: Arc<Inner<F>>,
+    /// The index of the waker of this clone in [`Wakers`], if it was polled already
+    key: Option<usize>,
+}
+
+impl<F: future::Future> Shared<F> {
+    /// Wrap a future
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub fn new(future: F) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: AtomicUsize::new(IDLE),
+                stage: UnsafeCell::new(Stage::Running(future)),
+                notifier: Arc::new(Notifier {
+                    locked: AtomicBool::new(false),
+                    wakers: UnsafeCell::new(Wakers {
+                        slots: Vec::new(),
+                        free: Vec::new(),
+                    }),
+                }),
+            }),
+            key: None,
+        }
+    }
+
+    /// The output of the inner future, if it completed
+    #[must_use]
+    pub fn peek(&self) -> Option<&F::Output> {
+        self.inner.output()
+    }
+}
+
+impl<F: future::Future> Clone for Shared<F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            key: None,
+        }
+    }
+}
+
+impl<F: future::Future> fmt::Debug for Shared<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.inner.state.load(Ordering::Acquire) {
+            IDLE => "Idle",
+            POLLING => "Polling",
+            _ => "Complete",
+        };
+        f.debug_struct("Shared")
+            .field("state", &state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> future::Future for Shared<F>
+where
+    F: future::Future,
+    F::Output: Clone,
+{
+    type Output = F::Output;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        let this = self.get_mut();
+        let inner = &*this.inner;
+        if let Some(output) = inner.output() {
+            return task::Poll::Ready(output.clone());
+        }
+
+        // Register first, so a wake-up of the clone that is polling the inner future is not missed.
+        inner.notifier.lock(|wakers| {
+            let key = *this.key.get_or_insert_with(|| wakers.insert());
+            wakers.register(key, cx.waker());
+        });
+        match inner
+            .state
+            .compare_exchange(IDLE, POLLING, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => {},
+            Err(COMPLETE) => return task::Poll::Ready(inner.output().unwrap().clone()),
+            Err(_) => return task::Poll::Pending,
+        }
+
+        // Reset the state, and let another clone try again, if the inner future panics.
+        struct Reset<'a, F: future::Future>(&'a Inner<F>);
+
+        impl<F: future::Future> Drop for Reset<'_, F> {
+            fn drop(&mut self) {
+                self.0.state.store(IDLE, Ordering::Release);
+                self.0.notifier.wake_all();
+            }
+        }
+
+        let reset = Reset(inner);
+        let waker = task::Waker::from(Arc::clone(&inner.notifier));
+        let mut cx = task::Context::from_waker(&waker);
+        // SAFETY: we own the stage while the state is `POLLING`, and `Inner` is never moved
+        let stage = unsafe { &mut *inner.stage.get() };
+        let Stage::Running(future) = stage else {
+            unreachable!();
+        };
+        let poll = unsafe { pin::Pin::new_unchecked(future) }.poll(&mut cx);
+        mem::forget(reset);
+        match poll {
+            task::Poll::Ready(output) => {
+                // The inner future is dropped in place.
+                *stage = Stage::Complete(output);
+                inner.state.store(COMPLETE, Ordering::Release);
+                inner.notifier.wake_all();
+                task::Poll::Ready(inner.output().unwrap().clone())
+            },
+            task::Poll::Pending => {
+                inner.state.store(IDLE, Ordering::Release);
+                task::Poll::Pending
+            },
+        }
+    }
+}
+
+impl<F: future::Future> Drop for Shared<F> {
+    fn drop(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+        let woken = self.inner.notifier.lock(|wakers| wakers.remove(key));
+        // This clone was woken up, but will not poll the inner future anymore,
+        // so pass the wake-up on to the other clones.
+        if woken && self.inner.state.load(Ordering::Acquire) == IDLE {
+            self.inner.notifier.wake_all();
+        }
+    }
+}
+
+/// The inner future is not being polled
+const IDLE: usize = 0;
+/// A clone is polling the inner future
+const POLLING: usize = 1;
+/// The inner future completed, and its output is stored
+const COMPLETE: usize = 2;
+
+struct Inner<F: future::Future> {
+    state: AtomicUsize,
+    stage: UnsafeCell<Stage<F>>,
+    notifier: Arc<Notifier>,
+}
+
+enum Stage<F: future::Future> {
+    Running(F),
+    Complete(F::Output),
+}
+
+// SAFETY: the future is only accessed by the clone that moved the state to `POLLING`,
+// and the output is only shared after the state was set to `COMPLETE`.
+unsafe impl<F> Send for Inner<F>
+where
+    F: future::Future + Send,
+    F::Output: Send + Sync,
+{
+}
+
+unsafe impl<F> Sync for Inner<F>
+where
+    F: future::Future + Send,
+    F::Output: Send + Sync,
+{
+}
+
+impl<F: future::Future> Inner<F> {
+    fn output(&self) -> Option<&F::Output> {
+        if self.state.load(Ordering::Acquire) != COMPLETE {
+            return None;
+        }
+        // SAFETY: the stage is never modified after the state was set to `COMPLETE`
+        match unsafe { &*self.stage.get() } {
+            Stage::Complete(output) => Some(output),
+            Stage::Running(_) => None,
+        }
+    }
+}
+
+/// The waker of the inner future, which wakes all clones that are waiting for it
+struct Notifier {
+    locked: AtomicBool,
+    wakers: UnsafeCell<Wakers>,
+}
+
+// SAFETY: the wakers are only accessed while the lock is held
+unsafe impl Send for Notifier {}
+unsafe impl Sync for Notifier {}
+
+impl Notifier {
+    /// Run `f` while holding the spin lock
+    ///
+    /// The critical sections are short and never wake a task, so a spin lock is sufficient,
+    /// and `Shared` works in `#![no_std]`.
+    fn lock<R>(&self, f: impl FnOnce(&mut Wakers) -> R) -> R {
+        struct Unlock<'a>(&'a AtomicBool);
+
+        impl Drop for Unlock<'_> {
+            fn drop(&mut self) {
+                self.0.store(false, Ordering::Release);
+            }
+        }
+
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+        let _unlock = Unlock(&self.locked);
+        // SAFETY: we hold the lock
+        f(unsafe { &mut *self.wakers.get() })
+    }
+
+    fn wake_all(&self) {
+        let wakers = self.lock(|wakers| wakers.take_all());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Wake for Notifier {
+    fn wake(self: Arc<Self>) {
+        self.wake_all();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_all();
+    }
+}
+
+/// A slab of the wakers of the clones that are waiting for the inner future
+struct Wakers {
+    slots: Vec<Option<task::Waker>>,
+    free: Vec<usize>,
+}
+
+impl Wakers {
+    fn insert(&mut self) -> usize {
+        self.free.pop().unwrap_or_else(|| {
+            self.slots.push(None);
+            self.slots.len() - 1
+        })
+    }
+
+    fn register(&mut self, key: usize, waker: &task::Waker) {
+        let slot = &mut self.slots[key];
+        if !matches!(slot, Some(old) if old.will_wake(waker)) {
+            *slot = Some(waker.clone());
+        }
+    }
+
+    /// Returns `true` if the waker was taken, i.e. the clone was woken up
+    fn remove(&mut self, key: usize) -> bool {
+        self.free.push(key);
+        self.slots[key].take().is_none()
+    }
+
+    fn take_all(&mut self) -> Vec<task::Waker> {
+        self.slots.iter_mut().filter_map(Option::take).collect()
+    }
+}
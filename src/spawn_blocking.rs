@@ -0,0 +1,286 @@
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use std::{fmt, future, pin, thread};
+
+/// A job that a [`BlockingExecutor`] runs to completion on some thread
+pub type BlockingTask = Box<dyn FnOnce() + Send + 'static>;
+
+/// Runs the body of a <code>#\[named_future(spawn_blocking)\]</code> function off the async thread
+///
+/// The executor is named by a path to a `static`, a `const`, or a unit struct, e.g.
+/// <code>#\[named_future(spawn_blocking = MY_POOL)\]</code>, so it lives for `'static`.
+/// Without a path, the [global `ThreadPool`](ThreadPool::global) is used.
+///
+/// An adapter for a runtime can forward the task to the blocking pool of the runtime,
+/// e.g. `tokio::task::spawn_blocking(task);`.
+pub trait BlockingExecutor {
+    /// Run `task` on another thread
+    ///
+    /// The task must eventually be run or dropped.
+    /// If it is dropped without running, then polling the future panics.
+    fn spawn_blocking(&'static self, task: BlockingTask);
+}
+
+/// A pool of threads for blocking jobs
+///
+/// A new thread is started if no thread is idle, until there are `max_threads` threads.
+/// After that, jobs are queued. A thread exits after it was idle for `keep_alive`.
+/// If no thread can be started, and no thread is running, then the queued jobs are dropped.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use named_future::ThreadPool;
+///
+/// static POOL: ThreadPool = ThreadPool::new(4, Duration::from_secs(10));
+/// ```
+pub struct ThreadPool {
+    state: Mutex<PoolState>,
+    condvar: Condvar,
+    max_threads: usize,
+    keep_alive: Duration,
+}
+
+struct PoolState {
+    /// `None` until the first job was queued, because `VecDeque::new()` is not `const`
+    queue: Option<VecDeque<BlockingTask>>,
+    /// The number of running threads
+    threads: usize,
+    /// The number of threads that are waiting for a job
+    idle: usize,
+    /// The number of idle threads that were notified, but did not wake up yet
+    notified: usize,
+}
+
+impl ThreadPool {
+    /// A pool of at most `max_threads` threads, which exit after they were idle for `keep_alive`
+    ///
+    /// No thread is started before the first job is spawned.
+    #[must_use]
+    pub const fn new(max_threads: usize, keep_alive: Duration) -> Self {
+        Self {
+            state: Mutex::new(PoolState {
+                queue: None,
+                threads: 0,
+                idle: 0,
+                notified: 0,
+            }),
+            condvar: Condvar::new(),
+            max_threads,
+            keep_alive,
+        }
+    }
+
+    /// The pool that is used if no other [`BlockingExecutor`] was given
+    ///
+    /// It has at most 512 threads, which exit after they were idle for 10 seconds.
+    #[must_use]
+    pub fn global() -> &'static Self {
+        static GLOBAL: ThreadPool = ThreadPool::new(512, Duration::from_secs(10));
+        &GLOBAL
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn run(&'static self) {
+        let mut state = self.lock();
+        loop {
+            if let Some(task) = state.queue.as_mut().and_then(VecDeque::pop_front) {
+                drop(state);
+                // A panicking job must not kill the thread.
+                let _ = panic::catch_unwind(AssertUnwindSafe(task));
+                state = self.lock();
+                continue;
+            }
+
+            state.idle += 1;
+            let (guard, result) = self
+                .condvar
+                .wait_timeout(state, self.keep_alive)
+                .unwrap_or_else(PoisonError::into_inner);
+            state = guard;
+            if state.notified > 0 {
+                // The spawner already removed this thread from the idle threads.
+                state.notified -= 1;
+            } else {
+                state.idle -= 1;
+                if result.timed_out() {
+                    state.threads -= 1;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl BlockingExecutor for ThreadPool {
+    fn spawn_blocking(&'static self, task: BlockingTask) {
+        let mut state = self.lock();
+        state
+            .queue
+            .get_or_insert_with(VecDeque::new)
+            .push_back(task);
+        if state.idle > 0 {
+            state.idle -= 1;
+            state.notified += 1;
+            drop(state);
+            self.condvar.notify_one();
+        } else if state.threads < self.max_threads {
+            state.threads += 1;
+            drop(state);
+            let spawned = thread::Builder::new()
+                .name("named-future-blocking".into())
+                .spawn(move || self.run());
+            if spawned.is_err() {
+                let mut state = self.lock();
+                state.threads -= 1;
+                if state.threads == 0 {
+                    // Nobody would run the queued jobs, so their futures are told by dropping them.
+                    let queue = state.queue.take();
+                    drop(state);
+                    drop(queue);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ThreadPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.lock();
+        f.debug_struct("ThreadPool")
+            .field("max_threads", &self.max_threads)
+            .field("keep_alive", &self.keep_alive)
+            .field("threads", &state.threads)
+            .field("idle", &state.idle)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Run `job` on `executor`, and return a future that resolves to its output
+///
+/// If the job panics, then the panic is resumed in the task that polls the future.
+/// If the future is dropped before the job started, then the job is not run.
+/// If the executor drops the job without running it, then polling the future panics.
+pub fn spawn_blocking<E, F, T>(executor: &'static E, job: F) -> BlockingJob<T>
+where
+    E: BlockingExecutor + ?Sized,
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(JobState {
+        output: None,
+        waker: None,
+        cancelled: false,
+        abandoned: false,
+    }));
+    let guard = JobGuard {
+        shared: Arc::clone(&shared),
+        finished: false,
+    };
+    executor.spawn_blocking(Box::new(move || {
+        let guard = guard;
+        if lock_job(&guard.shared).cancelled {
+            return;
+        }
+        let output = panic::catch_unwind(AssertUnwindSafe(job));
+        guard.finish(output);
+    }));
+    BlockingJob { shared }
+}
+
+/// The future returned by [`spawn_blocking()`]
+pub struct BlockingJob<T> {
+    shared: Arc<Mutex<JobState<T>>>,
+}
+
+struct JobState<T> {
+    output: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+    /// The future was dropped
+    cancelled: bool,
+    /// The task was dropped without running the job
+    abandoned: bool,
+}
+
+fn lock_job<T>(shared: &Mutex<JobState<T>>) -> MutexGuard<'_, JobState<T>> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Moved into the task, so the future learns if the executor dropped the task without running it
+struct JobGuard<T> {
+    shared: Arc<Mutex<JobState<T>>>,
+    finished: bool,
+}
+
+impl<T> JobGuard<T> {
+    fn finish(mut self, output: thread::Result<T>) {
+        self.finished = true;
+        let mut state = lock_job(&self.shared);
+        state.output = Some(output);
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for JobGuard<T> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let mut state = lock_job(&self.shared);
+        state.abandoned = true;
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> future::Future for BlockingJob<T> {
+    type Output = T;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = lock_job(&self.shared);
+        match state.output.take() {
+            Some(Ok(output)) => Poll::Ready(output),
+            Some(Err(payload)) => {
+                drop(state);
+                panic::resume_unwind(payload)
+            },
+            None if state.abandoned => {
+                drop(state);
+                panic!("the executor dropped the blocking job without running it")
+            },
+            None => {
+                match &state.waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => {},
+                    _ => state.waker = Some(cx.waker().clone()),
+                }
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl<T> Drop for BlockingJob<T> {
+    fn drop(&mut self) {
+        lock_job(&self.shared).cancelled = true;
+    }
+}
+
+impl<T> fmt::Debug for BlockingJob<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockingJob").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,79 @@
+use core::{fmt, future, pin, task};
+
+/// The error of a future whose deadline elapsed, see <code>#\[named_future(timeout)\]</code>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the deadline elapsed")
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl std::error::Error for Elapsed {}
+
+/// A future that returns `Err(`[`Elapsed`]`)` if its deadline completes first
+///
+/// The deadline is any future with the output `()`, usually a <code>[Timer]::[sleep](crate::Timer::sleep)()</code>.
+/// The inner future is polled first, so it wins if both are ready at the same time.
+///
+/// <code>#\[named_future(timeout)\]</code> generates a type alias `SlowMulTimeout<S>` for
+/// `Timeout<SlowMul, S>`, and a function `slow_mul_with_deadline(…, deadline: S)` to create it.
+///
+/// [Timer]: crate::Timer
+#[derive(Debug)]
+pub struct Timeout<F, S> {
+    future: F,
+    deadline: S,
+}
+
+impl<F, S> Timeout<F, S>
+where
+    F: future::Future,
+    S: future::Future<Output = ()>,
+{
+    /// Race `future` against `deadline`
+    #[inline]
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub fn new(future: F, deadline: S) -> Self {
+        Self { future, deadline }
+    }
+
+    /// The inner future
+    #[inline]
+    #[must_use]
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    /// The inner future, and the deadline
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> (F, S) {
+        (self.future, self.deadline)
+    }
+}
+
+impl<F, S> future::Future for Timeout<F, S>
+where
+    F: future::Future,
+    S: future::Future<Output = ()>,
+{
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<Self::Output> {
+        // SAFETY: both fields are structurally pinned, and `Timeout` does not implement `Drop`
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { pin::Pin::new_unchecked(&mut this.future) };
+        if let task::Poll::Ready(output) = future.poll(cx) {
+            return task::Poll::Ready(Ok(output));
+        }
+        let deadline = unsafe { pin::Pin::new_unchecked(&mut this.deadline) };
+        match deadline.poll(cx) {
+            task::Poll::Ready(()) => task::Poll::Ready(Err(Elapsed)),
+            task::Poll::Pending => task::Poll::Pending,
+        }
+    }
+}
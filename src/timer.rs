@@ -0,0 +1,220 @@
+use core::future;
+use core::time::Duration;
+
+/// A runtime-agnostic source of sleeps
+///
+/// Used to delay the next attempt of a [`Retry`](crate::Retry), e.g. with an
+/// [`ExponentialBackoff`](crate::ExponentialBackoff), and as the deadline of a
+/// [`Timeout`](crate::Timeout).
+///
+/// The crate ships a [`StdTimer`] that works with every executor (feature **`std`**),
+/// a [`TokioTimer`] (feature **`tokio`**), and an [`AsyncIoTimer`] (feature **`async-io`**).
+pub trait Timer {
+    /// A future that completes after the requested duration
+    type Sleep: future::Future<Output = ()>;
+
+    /// Sleep for `duration`
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+}
+
+impl<T: Timer + ?Sized> Timer for &T {
+    type Sleep = T::Sleep;
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        T::sleep(self, duration)
+    }
+}
+
+/// A [`Timer`] that spawns a thread for every sleep
+///
+/// This is a reference implementation that works with any executor, e.g. in tests.
+/// A sleep that is dropped early wakes up its thread, so no thread outlives its sleep.
+///
+/// Every sleep starts a new OS thread when it is polled for the first time, unless it is already
+/// over. A [`Timeout`](crate::Timeout) or an [`ExponentialBackoff`](crate::ExponentialBackoff)
+/// in a loop therefore starts one thread per attempt. Prefer the timer of your runtime if there
+/// is one.
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdTimer;
+
+#[cfg(feature = "std")]
+impl Timer for StdTimer {
+    type Sleep = std_timer::StdSleep;
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        std_timer::StdSleep::new(duration)
+    }
+}
+
+#[cfg(feature = "std")]
+mod std_timer {
+    use std::sync::{Arc, Condvar, Mutex};
+    use std::time::{Duration, Instant};
+    use std::{fmt, pin, task, thread};
+
+    /// The future returned by [`StdTimer::sleep()`](super::StdTimer)
+    pub struct StdSleep {
+        deadline: Option<Instant>,
+        shared: Option<Arc<Shared>>,
+    }
+
+    struct Shared {
+        state: Mutex<State>,
+        condvar: Condvar,
+    }
+
+    #[derive(Default)]
+    struct State {
+        waker: Option<task::Waker>,
+        elapsed: bool,
+        dropped: bool,
+    }
+
+    impl StdSleep {
+        pub(super) fn new(duration: Duration) -> Self {
+            Self {
+                // `None` if the deadline is not representable, i.e. it never elapses
+                deadline: Instant::now().checked_add(duration),
+                shared: None,
+            }
+        }
+    }
+
+    impl fmt::Debug for StdSleep {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("StdSleep")
+                .field("deadline", &self.deadline)
+                .finish_non_exhaustive()
+        }
+    }
+
+    impl std::future::Future for StdSleep {
+        type Output = ();
+
+        fn poll(self: pin::Pin<&mut Self>, cx: &mut task::Context<'_>) -> task::Poll<()> {
+            let this = self.get_mut();
+            let Some(deadline) = this.deadline else {
+                return task::Poll::Pending;
+            };
+            if Instant::now() >= deadline {
+                return task::Poll::Ready(());
+            }
+            let shared = this.shared.get_or_insert_with(|| {
+                let shared = Arc::new(Shared {
+                    state: Mutex::new(State::default()),
+                    condvar: Condvar::new(),
+                });
+                let thread_shared = Arc::clone(&shared);
+                thread::spawn(move || thread_shared.run(deadline));
+                shared
+            });
+            let mut state = shared.state.lock().unwrap_or_else(|err| err.into_inner());
+            if state.elapsed {
+                return task::Poll::Ready(());
+            }
+            match &state.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {},
+                _ => state.waker = Some(cx.waker().clone()),
+            }
+            task::Poll::Pending
+        }
+    }
+
+    impl Drop for StdSleep {
+        fn drop(&mut self) {
+            if let Some(shared) = &self.shared {
+                let mut state = shared.state.lock().unwrap_or_else(|err| err.into_inner());
+                state.dropped = true;
+                drop(state);
+                shared.condvar.notify_one();
+            }
+        }
+    }
+
+    impl Shared {
+        fn run(&self, deadline: Instant) {
+            let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+            loop {
+                if state.dropped {
+                    return;
+                }
+                let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                    break;
+                };
+                if timeout.is_zero() {
+                    break;
+                }
+                state = match self.condvar.wait_timeout(state, timeout) {
+                    Ok((state, _)) => state,
+                    Err(err) => err.into_inner().0,
+                };
+            }
+            state.elapsed = true;
+            let waker = state.waker.take();
+            drop(state);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub use std_timer::StdSleep;
+
+/// A [`Timer`] that uses [`tokio::time::sleep()`]
+///
+/// The sleeps must be created inside of a tokio runtime with the time driver enabled.
+#[cfg(feature = "tokio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioTimer;
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioTimer {
+    type Sleep = tokio::time::Sleep;
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        tokio::time::sleep(duration)
+    }
+}
+
+/// A [`Timer`] that uses [`async_io::Timer`]
+#[cfg(feature = "async-io")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-io")))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncIoTimer;
+
+#[cfg(feature = "async-io")]
+impl Timer for AsyncIoTimer {
+    type Sleep = AsyncIoSleep;
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        AsyncIoSleep(async_io::Timer::after(duration))
+    }
+}
+
+/// The future returned by [`AsyncIoTimer::sleep()`](AsyncIoTimer)
+#[cfg(feature = "async-io")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-io")))]
+#[derive(Debug)]
+pub struct AsyncIoSleep(async_io::Timer);
+
+#[cfg(feature = "async-io")]
+impl future::Future for AsyncIoSleep {
+    type Output = ();
+
+    #[inline]
+    fn poll(
+        mut self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<()> {
+        core::pin::Pin::new(&mut self.0).poll(cx).map(drop)
+    }
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use named_future_codegen::{Args, Flag, expand};
+
+fn main() {
+    let func: syn::ItemFn = syn::parse_quote! {
+        /// Multiply two numbers
+        ///
+        /// # Struct
+        ///
+        /// Future returned by [`generated_mul`]
+        pub async fn generated_mul(factor1: u32, factor2: u32) -> u32 {
+            futures_lite::future::yield_now().await;
+            factor1 * factor2
+        }
+    };
+    let mut args = Args::default();
+    args.send = Some(Flag::new("Send"));
+    args.name = Some(syn::parse_quote!(GeneratedMulFuture));
+    let tokens = expand(args, func).unwrap();
+
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    std::fs::write(out_dir.join("codegen.rs"), tokens.to_string()).unwrap();
+}
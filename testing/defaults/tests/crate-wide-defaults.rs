@@ -0,0 +1,41 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::rc::Rc;
+
+use futures_lite::future::{block_on, yield_now};
+use named_future::named_future;
+
+fn assert_send<T: Send>(value: T) -> T {
+    value
+}
+
+#[named_future]
+async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+    yield_now().await;
+    factor1 * factor2
+}
+
+#[named_future(!Send)]
+async fn count(counter: Rc<u32>) -> u32 {
+    yield_now().await;
+    *counter + 1
+}
+
+#[named_future(type = Answer, max_size = 4096)]
+async fn answer() -> [u8; 2048] {
+    let answer = [42; 2048];
+    yield_now().await;
+    answer
+}
+
+#[test]
+fn crate_wide_defaults() {
+    let future: SlowMulFuture = assert_send(slow_mul(6, 7));
+    assert_eq!(block_on(future), 42);
+
+    let future: CountFuture = count(Rc::new(41));
+    assert_eq!(block_on(future), 42);
+
+    let future: Answer = assert_send(answer());
+    assert_eq!(block_on(future), [42; 2048]);
+}
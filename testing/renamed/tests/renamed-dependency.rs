@@ -0,0 +1,25 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use futures_lite::future::{block_on, yield_now};
+use renamed_future::named_future;
+
+#[named_future(Send, Sync)]
+async fn answer() -> usize {
+    yield_now().await;
+    42
+}
+
+mod inner {
+    #[renamed_future::named_future(type = pub Question)]
+    pub async fn question(answer: usize) -> bool {
+        answer == 42
+    }
+}
+
+#[test]
+fn renamed_dependency() {
+    let future: Answer = answer();
+    let answer = block_on(future);
+    let future: inner::Question = inner::question(answer);
+    assert!(block_on(future));
+}
@@ -0,0 +1,145 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+use futures_lite::future::{block_on, poll_once, yield_now};
+use named_future::{AbortState, Aborted, NamedFuture, named_future};
+
+#[named_future(abortable)]
+async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+    yield_now().await;
+    factor1 * factor2
+}
+
+#[named_future(abortable, catch_unwind)]
+async fn may_panic(fail: bool) -> u32 {
+    yield_now().await;
+    if fail {
+        panic!("Oh no!");
+    }
+    42
+}
+
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Drops the `Arc` when the unnamed future is dropped
+#[named_future(abortable)]
+async fn holds(value: Arc<()>) {
+    yield_now().await;
+    drop(value);
+}
+
+#[test]
+fn not_aborted() {
+    let (future, handle) = slow_mul_abortable(6, 7);
+    assert!(!handle.is_aborted());
+    assert_eq!(block_on(future), Ok(42));
+    assert_eq!(block_on(slow_mul(6, 7)), Ok(42));
+    assert_eq!(block_on(holds(Arc::new(()))), Ok(()));
+    assert!(matches!(block_on(may_panic(false)), Ok(Ok(42))));
+}
+
+#[test]
+fn abort_before_poll() {
+    let (future, handle) = slow_mul_abortable(6, 7);
+    handle.abort();
+    assert!(handle.is_aborted());
+    assert_eq!(block_on(future), Err(Aborted));
+}
+
+#[test]
+fn abort_wakes_and_drops_in_place() {
+    let value = Arc::new(());
+    let (future, handle) = holds_abortable(Arc::clone(&value));
+    let mut future = Box::pin(future);
+
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let waker = Waker::from(Arc::clone(&counter));
+    let mut cx = Context::from_waker(&waker);
+    assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+    assert_eq!(Arc::strong_count(&value), 2);
+
+    let woken = counter.0.load(Ordering::SeqCst);
+    handle.clone().abort();
+    assert_eq!(counter.0.load(Ordering::SeqCst), woken + 1);
+
+    assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(Err(Aborted)));
+    assert_eq!(Arc::strong_count(&value), 1);
+    drop(future);
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+#[test]
+fn abort_from_another_thread() {
+    let (future, handle) = holds_abortable(Arc::new(()));
+    let mut future = Box::pin(future);
+    assert!(block_on(poll_once(future.as_mut())).is_none());
+    thread::spawn(move || handle.abort()).join().unwrap();
+    assert_eq!(block_on(future), Err(Aborted));
+}
+
+#[test]
+fn static_state() {
+    static STATE: AbortState = AbortState::new();
+
+    let (future, handle) = slow_mul_abortable_with(6, 7, &STATE);
+    assert!(!STATE.is_aborted());
+    handle.abort();
+    assert!(STATE.is_aborted());
+    assert_eq!(block_on(future), Err(Aborted));
+
+    STATE.reset();
+    let (future, handle) = slow_mul_abortable_with(6, 7, &STATE);
+    assert!(!handle.is_aborted());
+    assert_eq!(block_on(future), Ok(42));
+    assert_eq!(format!("{STATE:?}"), "AbortState { aborted: false, .. }");
+}
+
+#[test]
+fn abort_after_completion() {
+    let (future, handle) = slow_mul_abortable(6, 7);
+    assert_eq!(block_on(future), Ok(42));
+    handle.abort();
+}
+
+#[test]
+fn in_place() {
+    let future = SlowMul::boxed_pin((6, 7));
+    assert_eq!(block_on(future), Ok(42));
+}
+
+#[test]
+#[should_panic = "`SlowMul` was polled after it completed"]
+fn polled_after_abort() {
+    let (future, handle) = slow_mul_abortable(6, 7);
+    let mut future = Box::pin(future);
+    handle.abort();
+    assert_eq!(block_on(future.as_mut()), Err(Aborted));
+    let _ = block_on(future);
+}
+
+#[test]
+fn with_catch_unwind() {
+    std::panic::set_hook(Box::new(|_| ()));
+    let (future, _handle) = may_panic_abortable(true);
+    assert!(matches!(block_on(future), Ok(Err(_))));
+    let (future, handle) = may_panic_abortable(false);
+    handle.abort();
+    assert!(matches!(block_on(future), Err(Aborted)));
+}
+
+#[test]
+fn error() {
+    assert_eq!(Aborted.to_string(), "the future was aborted");
+    let _: &dyn std::error::Error = &Aborted;
+}
@@ -0,0 +1,81 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+use futures_lite::future::{block_on as lite_block_on, yield_now};
+use named_future::{NamedFuture, StdTimer, Storage, Timer, block_on, named_future};
+
+/// A slow multiplication
+#[named_future(blocking)]
+pub async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+    StdTimer.sleep(Duration::from_millis(1)).await;
+    factor1 * factor2
+}
+
+#[named_future(blocking, catch_unwind)]
+async fn maybe_panic(value: u32) -> u32 {
+    yield_now().await;
+    assert!(value > 0, "value must be positive");
+    value
+}
+
+#[named_future(blocking)]
+async fn first_word<'a>(text: &'a str) -> &'a str {
+    yield_now().await;
+    text.split(' ').next().unwrap()
+}
+
+#[named_future(blocking)]
+async fn not_send(value: Rc<u32>) -> u32 {
+    yield_now().await;
+    *value
+}
+
+#[derive(NamedFuture)]
+struct CountDeclared(Storage<Self>);
+
+#[named_future(into = CountDeclared, blocking)]
+async fn count(yields: u32) -> u32 {
+    for _ in 0..yields {
+        yield_now().await;
+    }
+    yields
+}
+
+#[test]
+fn blocking() {
+    assert_eq!(slow_mul_blocking(6, 7), 42);
+    assert_eq!(lite_block_on(slow_mul(6, 7)), 42);
+    assert_eq!(first_word_blocking("hello world"), "hello");
+    assert_eq!(lite_block_on(first_word("a b")), "a");
+    assert_eq!(not_send_blocking(Rc::new(42)), 42);
+    assert_eq!(lite_block_on(not_send(Rc::new(1))), 1);
+    assert_eq!(count_blocking(3), 3);
+    assert_eq!(lite_block_on(count(0)), 0);
+}
+
+#[test]
+fn catch_unwind() {
+    std::panic::set_hook(Box::new(|_| ()));
+    assert_eq!(maybe_panic_blocking(1).unwrap(), 1);
+    let payload = maybe_panic_blocking(0).unwrap_err();
+    assert_eq!(
+        payload.downcast_ref::<&str>().unwrap(),
+        &"value must be positive",
+    );
+    assert_eq!(lite_block_on(maybe_panic(2)).unwrap(), 2);
+}
+
+#[test]
+fn woken_by_other_thread() {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let value = block_on(async {
+        let sleep = StdTimer.sleep(Duration::from_millis(10));
+        thread::spawn(move || sender.send(42).unwrap());
+        sleep.await;
+        receiver.recv().unwrap()
+    });
+    assert_eq!(value, 42);
+}
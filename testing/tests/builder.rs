@@ -0,0 +1,94 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::future::IntoFuture;
+use std::time::Duration;
+
+use futures_lite::future::{block_on, yield_now};
+use named_future::{NamedFuture, Storage, named_future};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Method {
+    #[default]
+    Get,
+    Post,
+}
+
+/// Pretend to send a request
+#[named_future(builder, Send)]
+pub async fn request(
+    url: String,
+    #[default] method: Method,
+    #[default = Vec::new()] headers: Vec<(String, String)>,
+    #[default = Duration::from_secs(30)] timeout: Duration,
+    retries: u32,
+) -> String {
+    yield_now().await;
+    format!("{method:?} {url} {headers:?} {timeout:?} {retries}")
+}
+
+#[named_future(builder)]
+async fn concat<'a, 'b>(#[default = "?"] first: &'a str, second: &'b str) -> String {
+    format!("{first}{second}")
+}
+
+#[named_future(builder)]
+async fn nothing() -> u32 {
+    42
+}
+
+#[derive(NamedFuture)]
+struct AddDeclared(Storage<Self>);
+
+#[named_future(into = AddDeclared, builder)]
+async fn add(mut summand1: u32, #[default = 1] summand2: u32) -> u32 {
+    summand1 += summand2;
+    summand1
+}
+
+fn assert_send<T: Send>(value: T) -> T {
+    value
+}
+
+#[test]
+fn request_builder() {
+    let output = block_on(async {
+        request()
+            .url("https://example.com".to_owned())
+            .timeout(Duration::from_secs(5))
+            .retries(3)
+            .await
+    });
+    assert_eq!(output, "Get https://example.com [] 5s 3");
+
+    let builder: RequestBuilder<_, _> = request()
+        .retries(0)
+        .method(Method::Post)
+        .headers(vec![("a".to_owned(), "b".to_owned())])
+        .url("/".to_owned());
+    let future: Request = builder.into_future();
+    let output = block_on(assert_send(future));
+    assert_eq!(output, r#"Post / [("a", "b")] 30s 0"#);
+}
+
+#[test]
+fn lifetimes() {
+    let second = "b".to_owned();
+    assert_eq!(block_on(concat().second(&second).into_future()), "?b");
+    let first = "a".to_owned();
+    let future: Concat<'_, '_> = concat().second(&second).first(&first).into_future();
+    assert_eq!(block_on(future), "ab");
+}
+
+#[test]
+fn no_arguments() {
+    let builder: NothingBuilder = nothing();
+    assert_eq!(block_on(builder.into_future()), 42);
+}
+
+#[test]
+fn declared() {
+    let future: AddDeclared = add().summand1(2).into_future();
+    assert_eq!(block_on(future), 3);
+    assert_eq!(block_on(add().summand2(5).summand1(2).into_future()), 7);
+    assert_eq!(block_on(AddDeclared::new((1, 1))), 2);
+}
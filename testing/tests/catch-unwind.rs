@@ -0,0 +1,99 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::panic::{AssertUnwindSafe, catch_unwind, set_hook};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures_lite::future::{block_on, poll_once, yield_now};
+use named_future::{NamedFuture, Storage, named_future};
+
+struct IncrOnDrop(Arc<AtomicUsize>);
+
+impl Drop for IncrOnDrop {
+    fn drop(&mut self) {
+        self.0.fetch_add(100, Ordering::AcqRel);
+    }
+}
+
+#[named_future]
+async fn panic_uncaught(incr_on_drop: IncrOnDrop) -> usize {
+    incr_on_drop.0.fetch_add(10, Ordering::AcqRel);
+    panic!("Oh no!");
+}
+
+#[named_future(catch_unwind, Send, Sync)]
+async fn panic_caught(incr_on_drop: IncrOnDrop, fail: bool) -> usize {
+    incr_on_drop.0.fetch_add(10, Ordering::AcqRel);
+    yield_now().await;
+    if fail {
+        panic!("Oh no!");
+    }
+    incr_on_drop.0.load(Ordering::Acquire)
+}
+
+#[derive(NamedFuture)]
+struct PanicDeclared(Storage<Self>);
+
+#[named_future(into = PanicDeclared, catch_unwind)]
+async fn panic_declared() {
+    panic!("Declared!");
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => (*payload.downcast::<&'static str>().unwrap()).to_owned(),
+    }
+}
+
+#[test]
+fn polled_after_panic() {
+    set_hook(Box::new(|_| ()));
+    let counter = Arc::new(AtomicUsize::new(1));
+    let mut future = Box::pin(panic_uncaught(IncrOnDrop(Arc::clone(&counter))));
+
+    let err = catch_unwind(AssertUnwindSafe(|| block_on(future.as_mut()))).unwrap_err();
+    assert_eq!(panic_message(err), "Oh no!");
+    let err = catch_unwind(AssertUnwindSafe(|| block_on(future.as_mut()))).unwrap_err();
+    assert_eq!(
+        panic_message(err),
+        "`PanicUncaught` was polled after it panicked"
+    );
+
+    assert_eq!(counter.load(Ordering::Acquire), 111);
+    drop(future);
+    assert_eq!(counter.load(Ordering::Acquire), 111);
+}
+
+#[test]
+fn catch_unwind_option() {
+    set_hook(Box::new(|_| ()));
+    let counter = Arc::new(AtomicUsize::new(1));
+    let future = panic_caught(IncrOnDrop(Arc::clone(&counter)), false);
+    assert_eq!(block_on(future).unwrap(), 11);
+    assert_eq!(counter.load(Ordering::Acquire), 111);
+
+    let counter = Arc::new(AtomicUsize::new(1));
+    let mut future = Box::pin(panic_caught(IncrOnDrop(Arc::clone(&counter)), true));
+    let err = block_on(future.as_mut()).unwrap_err();
+    assert_eq!(panic_message(err), "Oh no!");
+    // The inner future was dropped immediately.
+    assert_eq!(counter.load(Ordering::Acquire), 111);
+
+    let err = catch_unwind(AssertUnwindSafe(|| block_on(poll_once(future.as_mut())))).unwrap_err();
+    assert_eq!(
+        panic_message(err),
+        "`PanicCaught` was polled after it completed"
+    );
+    drop(future);
+    assert_eq!(counter.load(Ordering::Acquire), 111);
+
+    let mut future = Box::pin(panic_declared());
+    let err = block_on(future.as_mut()).unwrap_err();
+    assert_eq!(panic_message(err), "Declared!");
+    let err = catch_unwind(AssertUnwindSafe(|| block_on(poll_once(future.as_mut())))).unwrap_err();
+    assert_eq!(
+        panic_message(err),
+        "`PanicDeclared` was polled after it completed"
+    );
+}
@@ -0,0 +1,81 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+#![cfg(any(debug_assertions, feature = "checked"))]
+
+use std::future::Future;
+use std::mem::ManuallyDrop;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::Pin;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::task::{Context, Poll};
+
+use futures_lite::future::{block_on, poll_fn, poll_once, yield_now};
+use named_future::named_future;
+
+#[named_future]
+async fn yield_twice(value: u32) -> u32 {
+    yield_now().await;
+    yield_now().await;
+    value
+}
+
+fn poll_unchecked<F: Future>(future: &mut F) -> Poll<F::Output> {
+    block_on(poll_fn(|cx: &mut Context<'_>| {
+        Poll::Ready(unsafe { Pin::new_unchecked(&mut *future) }.poll(cx))
+    }))
+}
+
+fn panic_message<R>(f: impl FnOnce() -> R) -> String {
+    let err = catch_unwind(AssertUnwindSafe(f)).err().unwrap();
+    match err.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(err) => (*err.downcast::<&'static str>().unwrap()).to_owned(),
+    }
+}
+
+#[cfg_attr(miri, ignore)] // intentionally breaks the rules
+#[test]
+fn moved_after_poll() {
+    let mut future = yield_twice(1);
+    assert!(poll_unchecked(&mut future).is_pending());
+
+    let mut moved = ManuallyDrop::new(Box::new(future));
+    let msg = panic_message(|| poll_unchecked(&mut **moved));
+    assert_eq!(
+        msg,
+        "`YieldTwice` was moved after it was polled for the first time"
+    );
+}
+
+#[cfg_attr(miri, ignore)] // intentionally breaks the rules
+#[test]
+fn polled_after_drop() {
+    let mut future = ManuallyDrop::new(yield_twice(2));
+    assert!(poll_unchecked(&mut *future).is_pending());
+    unsafe { ManuallyDrop::drop(&mut future) };
+
+    let msg = panic_message(|| poll_unchecked(&mut *future));
+    assert_eq!(msg, "`YieldTwice` was polled after it was dropped");
+}
+
+static REENTRANT: AtomicPtr<Reentrant> = AtomicPtr::new(ptr::null_mut());
+
+#[named_future]
+async fn reentrant() {
+    let this = REENTRANT.load(Ordering::Acquire);
+    let _ = poll_once(unsafe { Pin::new_unchecked(&mut *this) }).await;
+}
+
+#[cfg_attr(miri, ignore)] // intentionally breaks the rules
+#[test]
+fn polled_reentrantly() {
+    let mut future = Box::pin(reentrant());
+    let this = unsafe { future.as_mut().get_unchecked_mut() };
+    REENTRANT.store(this, Ordering::Release);
+
+    let msg = panic_message(|| block_on(future.as_mut()));
+    assert_eq!(
+        msg,
+        "`Reentrant` was polled while it was already being polled"
+    );
+}
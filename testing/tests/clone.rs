@@ -0,0 +1,73 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::panic::{AssertUnwindSafe, catch_unwind, set_hook};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures_lite::future::{block_on, poll_once, yield_now};
+use named_future::{NamedFuture, named_future};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[named_future(Clone, Send, Sync)]
+async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    yield_now().await;
+    factor1 * factor2
+}
+
+#[named_future(Clone)]
+async fn concat<'a>(prefix: &'a str, suffix: String) -> String {
+    yield_now().await;
+    format!("{prefix}{suffix}")
+}
+
+#[test]
+fn fan_out() {
+    let before = CALLS.load(Ordering::SeqCst);
+    let future = slow_mul(6, 7);
+    let clones = [future.clone(), future.clone()];
+    assert_eq!(CALLS.load(Ordering::SeqCst), before);
+    assert_eq!(block_on(future), 42);
+    for clone in clones {
+        assert_eq!(block_on(clone), 42);
+    }
+    assert_eq!(CALLS.load(Ordering::SeqCst), before + 3);
+}
+
+#[test]
+fn references() {
+    let prefix = "Hello, ".to_owned();
+    let future = concat(&prefix, "world!".to_owned());
+    assert_eq!(block_on(future.clone()), "Hello, world!");
+    assert_eq!(block_on(future), "Hello, world!");
+}
+
+#[test]
+fn in_place() {
+    let future = SlowMul::boxed_pin((6, 7));
+    let clone = future.try_clone().unwrap();
+    assert_eq!(block_on(future), 42);
+    assert_eq!(block_on(clone), 42);
+}
+
+#[test]
+fn started() {
+    set_hook(Box::new(|_| ()));
+    let mut future = Box::pin(slow_mul(6, 7));
+    assert!(future.try_clone().is_some());
+    assert!(block_on(poll_once(future.as_mut())).is_none());
+    assert!(future.try_clone().is_none());
+
+    let msg = catch_unwind(AssertUnwindSafe(|| future.clone())).unwrap_err();
+    assert_eq!(
+        msg.downcast_ref::<&str>().unwrap(),
+        &"`SlowMul` was cloned after it was polled",
+    );
+    assert_eq!(block_on(future), 42);
+}
+
+#[test]
+fn dropped_before_start() {
+    drop(slow_mul(6, 7));
+    drop(concat("", String::new()).clone());
+}
@@ -0,0 +1,15 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use futures_lite::future::block_on;
+
+include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
+
+fn assert_send<T: Send>(value: T) -> T {
+    value
+}
+
+#[test]
+fn codegen() {
+    let future: GeneratedMulFuture = assert_send(generated_mul(6, 7));
+    assert_eq!(block_on(future), 42);
+}
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures_lite::future::{block_on, or, ready, yield_now};
+use named_future::named_future_fn;
+
+fn assert_send_sync<T: Send + Sync>(value: T) -> T {
+    value
+}
+
+named_future_fn! {
+    /// Calculate `factor1 * factor2 + summand` asynchronously
+    async fn calculate(factor1: u32, factor2: u32, summand: u32) -> u32
+    as
+    /// Future returned by [`calculate`]
+    pub CalculateFuture: Send + Sync
+    {
+        let a = factor1;
+        yield_now().await;
+        let b = a * factor2;
+        yield_now().await;
+        b + summand
+    }
+}
+
+named_future_fn! {
+    async fn copy<'a, 'b>(dest: &'a mut u32, src: &'b u32,) as Copy {
+        yield_now().await;
+        *dest = *src;
+    }
+}
+
+struct IncrOnDrop(Arc<AtomicUsize>);
+
+impl Drop for IncrOnDrop {
+    fn drop(&mut self) {
+        self.0.fetch_add(100, Ordering::AcqRel);
+    }
+}
+
+named_future_fn! {
+    async fn increment_twice(incr_on_drop: IncrOnDrop) -> usize as IncrementTwice: Send {
+        incr_on_drop.0.fetch_add(10, Ordering::AcqRel);
+        yield_now().await;
+        incr_on_drop.0.fetch_add(1000, Ordering::AcqRel);
+        panic!("Should not be reached!");
+    }
+}
+
+#[test]
+fn declarative() {
+    let future: CalculateFuture = assert_send_sync(calculate(12, 34, 56));
+    assert_eq!(block_on(future), 12 * 34 + 56);
+    assert_eq!(
+        format!("{:?}", calculate(1, 2, 3)),
+        "CalculateFuture { .. }"
+    );
+
+    let mut dest = 47;
+    let src = 11;
+    let future: Copy<'_, '_> = copy(&mut dest, &src);
+    block_on(future);
+    assert_eq!(dest, src);
+
+    let counter = Arc::new(AtomicUsize::new(1));
+    let future = increment_twice(IncrOnDrop(Arc::clone(&counter)));
+    assert_eq!(block_on(or(future, ready(2))), 2);
+    assert_eq!(counter.load(Ordering::Acquire), 111);
+}
@@ -8,7 +8,7 @@ use named_future::named_future;
 /// # Struct
 ///
 /// Future returned by [`calculate`]
-#[named_future(Send, Sync, Type = pub CalculateFuture, Crate = ::named_future)]
+#[named_future(Send, Sync, type = pub CalculateFuture, crate = ::named_future)]
 async fn calculate<T>(factor1: T, factor2: T, summand: T) -> T
 where
     T: core::ops::Add + core::ops::Mul,
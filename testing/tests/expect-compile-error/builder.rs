@@ -0,0 +1,26 @@
+use futures_lite::future::block_on;
+use named_future::named_future;
+
+#[named_future]
+async fn no_builder(#[default] value: u32) -> u32 {
+    value
+}
+
+#[named_future(builder)]
+async fn list(#[default(1)] value: u32) -> u32 {
+    value
+}
+
+#[named_future(builder)]
+async fn duplicate(#[default] #[default = 2] value: u32) -> u32 {
+    value
+}
+
+#[named_future(builder)]
+async fn required(first: u32, #[default] second: u32) -> u32 {
+    first + second
+}
+
+fn main() {
+    block_on(async { required().second(1).await });
+}
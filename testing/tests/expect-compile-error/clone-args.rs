@@ -0,0 +1,13 @@
+use std::sync::Mutex;
+
+use futures_lite::future::block_on;
+use named_future::named_future;
+
+#[named_future(Clone)]
+async fn lock(value: Mutex<u32>) -> u32 {
+    *value.lock().unwrap()
+}
+
+fn main() {
+    block_on(lock(Mutex::new(42)));
+}
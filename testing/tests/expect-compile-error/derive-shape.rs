@@ -0,0 +1,21 @@
+use named_future::{NamedFuture, Storage};
+
+#[derive(NamedFuture)]
+struct Named {
+    storage: Storage<Self>,
+}
+
+#[derive(NamedFuture)]
+struct TwoFields(Storage<Self>, u32);
+
+#[derive(NamedFuture)]
+enum Enum {}
+
+#[derive(NamedFuture)]
+struct Generic<'a, T, const N: usize>(Storage<Self>, &'a [T; N]);
+
+#[derive(NamedFuture)]
+#[repr(C, packed)]
+struct Packed(Storage<Self>);
+
+fn main() {}
@@ -0,0 +1,29 @@
+use futures_lite::future::block_on;
+use named_future::named_future;
+
+#[named_future(Snd, Type = pub Answer, Send, !Send, !crate = ::named_future, , max_size = -1)]
+async fn answer() -> usize {
+    42
+}
+
+#[named_future(Crate = ::named_future, unknown_option)]
+async fn question() -> usize {
+    42
+}
+
+#[named_future(sidecar = u32)]
+async fn sidecar_without_callback() {}
+
+#[named_future(into = Declared, on_cancel = drop, sidecar = u32)]
+async fn sidecar_with_into() {}
+
+#[named_future(into = Declared, abortable)]
+async fn abortable_with_into() {}
+
+#[named_future(Clone, abortable)]
+async fn clone_with_abortable() {}
+
+fn main() {
+    assert_eq!(42, block_on(answer()));
+    assert_eq!(42, block_on(question()));
+}
@@ -0,0 +1,64 @@
+use named_future::named_future;
+
+#[named_future(service)]
+async fn no_request() -> Result<(), ()> {
+    Ok(())
+}
+
+#[named_future(service)]
+async fn too_many(state: u32, req: u32, extra: u32) -> Result<u32, ()> {
+    Ok(state + req + extra)
+}
+
+#[named_future(service)]
+async fn borrows<'a>(req: &'a str) -> Result<usize, ()> {
+    Ok(req.len())
+}
+
+#[named_future(service, layer)]
+async fn no_inner(req: u32) -> Result<u32, ()> {
+    Ok(req)
+}
+
+#[named_future(layer)]
+async fn no_service(inner: u32, req: u32) -> Result<u32, ()> {
+    Ok(inner + req)
+}
+
+#[named_future(service)]
+async fn not_result(req: u32) -> u32 {
+    req
+}
+
+#[derive(Clone)]
+struct Api;
+
+#[named_future]
+impl Api {
+    async fn no_option(&self, req: u32) -> Result<u32, ()> {
+        Ok(req)
+    }
+}
+
+#[named_future(service)]
+impl Api {
+    fn not_async(&self, req: u32) -> Result<u32, ()> {
+        Ok(req)
+    }
+}
+
+#[named_future(service)]
+impl Api {
+    async fn no_request(&self) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+#[named_future(service)]
+impl Default for Api {
+    fn default() -> Self {
+        Api
+    }
+}
+
+fn main() {}
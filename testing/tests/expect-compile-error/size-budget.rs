@@ -0,0 +1,13 @@
+use futures_lite::future::{block_on, yield_now};
+use named_future::named_future;
+
+#[named_future(max_size = 64)]
+async fn big() -> usize {
+    let data = [42; 128];
+    yield_now().await;
+    data.len()
+}
+
+fn main() {
+    assert_eq!(128, block_on(big()));
+}
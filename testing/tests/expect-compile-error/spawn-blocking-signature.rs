@@ -0,0 +1,18 @@
+use named_future::named_future;
+
+#[named_future(spawn_blocking)]
+async fn is_async(value: u32) -> u32 {
+    value
+}
+
+#[named_future(spawn_blocking)]
+fn borrows<'a>(value: &'a str) -> usize {
+    value.len()
+}
+
+#[named_future]
+fn not_async(value: u32) -> u32 {
+    value
+}
+
+fn main() {}
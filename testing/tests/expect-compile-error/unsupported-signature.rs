@@ -0,0 +1,32 @@
+use named_future::named_future;
+
+#[named_future]
+fn not_async(value: u32) -> u32 {
+    value
+}
+
+#[named_future]
+const unsafe extern "C" fn everything_wrong<T: Copy, const N: usize>(value: T) -> T {
+    value
+}
+
+#[named_future]
+async fn patterns((a, b): (u32, u32), ref c: u32, _: u32, d: impl Copy) -> u32 {
+    a + b + c
+}
+
+#[named_future]
+async fn variadic(value: u32, _: ...) -> u32 {
+    value
+}
+
+struct Counter(u32);
+
+impl Counter {
+    #[named_future]
+    async fn get(&self) -> u32 {
+        self.0
+    }
+}
+
+fn main() {}
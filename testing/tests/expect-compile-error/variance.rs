@@ -0,0 +1,34 @@
+use std::cell::Cell;
+
+use named_future::{named_future, named_future_fn};
+
+#[named_future]
+async fn forward<'a, 'b>(slot: &'a mut &'b str) -> &'a mut &'b str {
+    slot
+}
+
+#[named_future]
+async fn in_cell<'a>(cell: Cell<&'a str>) -> usize {
+    cell.get().len()
+}
+
+named_future_fn! {
+    async fn declarative<'a, 'b>(slot: &'a mut &'b str) -> &'a mut &'b str as Declarative {
+        slot
+    }
+}
+
+// Would allow writing a short-lived reference into a `&'static str`.
+fn shrink_forward<'a, 'short>(future: Forward<'a, 'static>) -> Forward<'a, 'short> {
+    future
+}
+
+fn shrink_in_cell<'short>(future: InCell<'static>) -> InCell<'short> {
+    future
+}
+
+fn shrink_declarative<'a, 'short>(future: Declarative<'a, 'static>) -> Declarative<'a, 'short> {
+    future
+}
+
+fn main() {}
@@ -0,0 +1,84 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::mem::MaybeUninit;
+use std::pin::Pin;
+use std::{mem, ptr, thread};
+
+use futures_lite::future::{block_on, yield_now};
+use named_future::{NamedFuture, Storage, named_future, named_future_fn};
+
+#[named_future]
+async fn large(value: u8) -> usize {
+    let buffer = [value; 20 * 1024];
+    yield_now().await;
+    buffer.iter().map(|&b| usize::from(b)).sum()
+}
+
+/// Only constructed in place, so that the future never lives on the stack
+#[allow(dead_code)]
+#[named_future]
+async fn huge(value: u8) -> usize {
+    let buffer = [value; 1024 * 1024];
+    yield_now().await;
+    buffer.iter().map(|&b| usize::from(b)).sum()
+}
+
+#[derive(NamedFuture)]
+struct LargeDeclared(Storage<Self>);
+
+#[named_future(into = LargeDeclared)]
+async fn large_declared(value: u8) -> usize {
+    large(value).await
+}
+
+named_future_fn! {
+    async fn large_declarative(value: u8) -> usize as LargeDeclarative {
+        large(value).await
+    }
+}
+
+#[test]
+fn boxed_pin() {
+    let future = Large::boxed_pin((2,));
+    assert_eq!(block_on(future), 2 * 20 * 1024);
+
+    assert_eq!(block_on(large_declared(1)), 20 * 1024);
+    let future = LargeDeclared::boxed_pin((3,));
+    assert_eq!(block_on(future), 3 * 20 * 1024);
+
+    let future = LargeDeclarative::boxed_pin((4,));
+    assert_eq!(block_on(future), 4 * 20 * 1024);
+}
+
+#[test]
+fn init_in() {
+    let mut slot = MaybeUninit::<Large>::uninit();
+    let slot = unsafe { Pin::new_unchecked(&mut slot) };
+    let mut future = unsafe { Large::init_in(slot, (5,)) };
+    assert_eq!(block_on(future.as_mut()), 5 * 20 * 1024);
+    unsafe { ptr::drop_in_place(future.get_unchecked_mut()) };
+}
+
+// Without optimizations, the future is created on the stack before it is written into the slot.
+#[cfg_attr(any(debug_assertions, miri), ignore = "needs optimizations")]
+#[test]
+fn smaller_stack() {
+    assert!(mem::size_of::<Huge>() > 1024 * 1024);
+    let thread = thread::Builder::new().stack_size(256 * 1024);
+    let sum = thread
+        .spawn(|| {
+            let boxed = block_on(Huge::boxed_pin((1,)));
+
+            let mut slot = Box::new(MaybeUninit::<Huge>::uninit());
+            let slot = unsafe { Pin::new_unchecked(&mut *slot) };
+            let mut future = unsafe { Huge::init_in(slot, (2,)) };
+            let in_place = block_on(future.as_mut());
+            unsafe { ptr::drop_in_place(future.get_unchecked_mut()) };
+
+            boxed + in_place
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(sum, 3 * 1024 * 1024);
+}
@@ -0,0 +1,73 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::panic::{AssertUnwindSafe, catch_unwind, set_hook};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+
+use futures_lite::future::{block_on, poll_once, yield_now};
+use named_future::{Lazy, named_future};
+
+#[named_future(Send)]
+async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+    yield_now().await;
+    factor1 * factor2
+}
+
+#[named_future]
+async fn not_send(value: Arc<u32>) -> u32 {
+    let local = Rc::new(*value);
+    yield_now().await;
+    *local
+}
+
+fn assert_unpin<T: Unpin>(_: &T) {}
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[test]
+fn lazy() {
+    let mut future = Lazy::<SlowMul>::new((6, 7));
+    assert_unpin(&future);
+    assert_send(&future);
+    assert!(!future.is_started());
+    assert_eq!(future.args(), Some(&(6, 7)));
+
+    assert!(block_on(poll_once(&mut future)).is_none());
+    assert!(future.is_started());
+    assert_eq!(future.args(), None);
+    assert_eq!(block_on(future), 42);
+    assert_eq!(block_on(slow_mul(6, 7)), 42);
+}
+
+#[test]
+fn move_to_thread() {
+    let future = Lazy::<SlowMul>::new((6, 7));
+    assert_eq!(thread::spawn(move || block_on(future)).join().unwrap(), 42);
+}
+
+#[test]
+fn not_started() {
+    let value = Arc::new(42);
+    let future = Lazy::<NotSend>::new((Arc::clone(&value),));
+    assert_unpin(&future);
+    assert_eq!(Arc::strong_count(&value), 2);
+    drop(future);
+    assert_eq!(Arc::strong_count(&value), 1);
+
+    let future = Lazy::<NotSend>::new((Arc::clone(&value),));
+    assert_eq!(block_on(future), 42);
+    assert_eq!(block_on(not_send(value)), 42);
+}
+
+#[test]
+fn polled_after_completion() {
+    set_hook(Box::new(|_| ()));
+    let mut future = Lazy::<SlowMul>::new((6, 7));
+    assert_eq!(block_on(&mut future), 42);
+    let msg = catch_unwind(AssertUnwindSafe(|| block_on(future))).unwrap_err();
+    assert_eq!(
+        msg.downcast_ref::<String>().unwrap(),
+        "`SlowMul` was polled after it completed",
+    );
+}
@@ -0,0 +1,47 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+#![allow(clippy::just_underscores_and_digits)]
+
+use futures_lite::future::{block_on, yield_now};
+use named_future::named_future;
+
+macro_rules! named_futures {
+    ($($vis:vis $name:ident($($arg:ident: $ty:ty),*) -> $ret:ty { $($body:tt)* })*) => {$(
+        #[named_future(Send, Sync)]
+        $vis async fn $name($($arg: $ty),*) -> $ret {
+            yield_now().await;
+            $($body)*
+        }
+    )*};
+}
+
+named_futures! {
+    args(args: u32, fut: u32) -> u32 { args + fut }
+    cx(cx: u32, f: u32) -> u32 { cx * f }
+    fut(__implementation: u32, _future: u32) -> u32 { __implementation - _future }
+    f(_pin: u32, _phantom: u32, this: u32) -> u32 { _pin + _phantom + this }
+}
+
+mod inner {
+    use super::*;
+
+    named_futures! {
+        pub args(self_: u32) -> u32 { self_ }
+        pub __implementation(_0: u32, _1: u32) -> u32 { _0 + _1 }
+    }
+}
+
+#[test]
+fn macro_rules() {
+    let future: Args = args(40, 2);
+    assert_eq!(block_on(future), 42);
+    let future: Cx = cx(6, 7);
+    assert_eq!(block_on(future), 42);
+    let future: Fut = fut(50, 8);
+    assert_eq!(block_on(future), 42);
+    let future: F = f(20, 20, 2);
+    assert_eq!(block_on(future), 42);
+    let future: inner::Args = inner::args(42);
+    assert_eq!(block_on(future), 42);
+    let future: inner::Implementation = inner::__implementation(40, 2);
+    assert_eq!(block_on(future), 42);
+}
@@ -0,0 +1,100 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::collections::BTreeMap;
+use std::panic::{AssertUnwindSafe, catch_unwind, set_hook};
+use std::sync::Mutex;
+
+use futures_lite::future::{block_on, poll_once, yield_now};
+use named_future::{NamedFuture, Storage, named_future};
+
+static CANCELLED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn cancelled(name: &'static str) {
+    CANCELLED.lock().unwrap().push(name.to_owned());
+}
+
+fn cancelled_with_id(name: &'static str, id: &Option<u32>) {
+    CANCELLED.lock().unwrap().push(format!("{name} {id:?}"));
+}
+
+fn cancelled_with_tags(name: &'static str, tags: &BTreeMap<String, u32>) {
+    CANCELLED.lock().unwrap().push(format!("{name} {tags:?}"));
+}
+
+fn take_cancelled() -> Vec<String> {
+    std::mem::take(&mut *CANCELLED.lock().unwrap())
+}
+
+#[named_future(on_cancel = cancelled)]
+async fn yield_once(fail: bool) -> u32 {
+    yield_now().await;
+    if fail {
+        panic!("Oh no!");
+    }
+    42
+}
+
+#[named_future(on_cancel = cancelled_with_id, sidecar = Option<u32>)]
+async fn with_sidecar() {
+    yield_now().await;
+}
+
+#[named_future(sidecar = BTreeMap<String, u32>, on_cancel = cancelled_with_tags)]
+async fn with_tags() {
+    yield_now().await;
+}
+
+#[derive(NamedFuture)]
+struct Declared(Storage<Self>);
+
+#[named_future(into = Declared, on_cancel = self::cancelled)]
+async fn declared() {
+    yield_now().await;
+}
+
+// All cases are tested in one function, because the callbacks are recorded globally.
+#[test]
+fn on_cancel() {
+    set_hook(Box::new(|_| ()));
+
+    assert_eq!(block_on(yield_once(false)), 42);
+    assert_eq!(take_cancelled(), Vec::<String>::new());
+
+    drop(yield_once(false));
+    assert_eq!(take_cancelled(), ["YieldOnce"]);
+
+    let mut future = Box::pin(yield_once(false));
+    assert!(block_on(poll_once(future.as_mut())).is_none());
+    drop(future);
+    assert_eq!(take_cancelled(), ["YieldOnce"]);
+
+    let mut future = Box::pin(yield_once(true));
+    let _ = catch_unwind(AssertUnwindSafe(|| block_on(future.as_mut()))).unwrap_err();
+    drop(future);
+    assert_eq!(take_cancelled(), Vec::<String>::new());
+
+    let mut future = Box::pin(with_sidecar());
+    assert_eq!(*future.sidecar(), None);
+    *future.as_mut().sidecar_mut() = Some(7);
+    assert!(block_on(poll_once(future.as_mut())).is_none());
+    drop(future);
+    assert_eq!(take_cancelled(), ["WithSidecar Some(7)"]);
+
+    let future = WithSidecar::boxed_pin(());
+    assert_eq!(*future.sidecar(), None);
+    block_on(future);
+    assert_eq!(take_cancelled(), Vec::<String>::new());
+
+    let mut future = Box::pin(with_tags());
+    assert!(future.sidecar().is_empty());
+    future
+        .as_mut()
+        .sidecar_mut()
+        .insert("retries".to_owned(), 3);
+    drop(future);
+    assert_eq!(take_cancelled(), [r#"WithTags {"retries": 3}"#]);
+
+    drop(declared());
+    block_on(declared());
+    assert_eq!(take_cancelled(), ["Declared"]);
+}
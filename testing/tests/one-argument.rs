@@ -1,3 +1,5 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -1,3 +1,5 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
 use std::panic::{catch_unwind, set_hook};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
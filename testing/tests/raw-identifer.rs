@@ -1,3 +1,4 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
 #![allow(non_camel_case_types)]
 
 use futures_lite::future::block_on;
@@ -1,3 +1,5 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
 use futures_lite::future::block_on;
 use named_future::named_future;
 
@@ -0,0 +1,143 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::panic::{AssertUnwindSafe, catch_unwind, set_hook};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_lite::future::{YieldNow, block_on, yield_now};
+use named_future::{
+    Attempts, ExponentialBackoff, NamedFuture, Retry, Storage, Timer, named_future,
+};
+
+/// Fails until the `succeed_at`-th call
+#[named_future(retry)]
+async fn flaky(calls: Arc<AtomicU32>, succeed_at: u32) -> Result<u32, String> {
+    let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+    yield_now().await;
+    if call >= succeed_at {
+        Ok(call)
+    } else {
+        Err(format!("call {call} failed"))
+    }
+}
+
+#[named_future(retry)]
+async fn first_char<'a>(text: &'a str) -> Result<&'a str, ()> {
+    text.get(..1).ok_or(())
+}
+
+#[derive(NamedFuture)]
+struct ParseDeclared(Storage<Self>);
+
+#[named_future(into = ParseDeclared, retry)]
+async fn parse(text: String) -> Result<u32, std::num::ParseIntError> {
+    text.parse()
+}
+
+/// Records the requested delays, and only yields once
+#[derive(Default)]
+struct TestTimer(Mutex<Vec<Duration>>);
+
+impl Timer for TestTimer {
+    type Sleep = YieldNow;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        self.0.lock().unwrap().push(duration);
+        yield_now()
+    }
+}
+
+#[test]
+fn attempts() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let future: FlakyRetry<Attempts> = flaky_retry(Arc::clone(&calls), 3, Attempts(3));
+    assert_eq!(block_on(future), Ok(3));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    let calls = Arc::new(AtomicU32::new(0));
+    let future = flaky_retry(Arc::clone(&calls), 3, Attempts(2));
+    assert_eq!(block_on(future), Err("call 2 failed".to_owned()));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let calls = Arc::new(AtomicU32::new(0));
+    assert_eq!(block_on(flaky(Arc::clone(&calls), 1)), Ok(1));
+}
+
+#[test]
+fn closure() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let mut seen = Vec::new();
+    let future = flaky_retry(Arc::clone(&calls), 10, |attempt, error: &String| {
+        seen.push((attempt, error.clone()));
+        attempt < 2
+    });
+    assert_eq!(block_on(future), Err("call 2 failed".to_owned()));
+    assert_eq!(seen, [
+        (1, "call 1 failed".to_owned()),
+        (2, "call 2 failed".to_owned())
+    ],);
+}
+
+#[test]
+fn backoff() {
+    let timer = TestTimer::default();
+    let policy = ExponentialBackoff::new(&timer, 5, Duration::from_millis(10))
+        .with_max_delay(Duration::from_millis(25));
+    let calls = Arc::new(AtomicU32::new(0));
+    let mut future = Box::pin(flaky_retry(Arc::clone(&calls), 4, policy));
+    assert_eq!(block_on(future.as_mut()), Ok(4));
+    assert_eq!(future.attempts(), 4);
+    assert_eq!(
+        *timer.0.lock().unwrap(),
+        [10, 20, 25].map(Duration::from_millis),
+    );
+}
+
+#[test]
+fn references() {
+    let text = "hello".to_owned();
+    assert_eq!(block_on(first_char_retry(&text, Attempts(1))), Ok("h"));
+    assert_eq!(block_on(first_char_retry("", Attempts(3))), Err(()));
+    assert_eq!(block_on(first_char(&text)), Ok("h"));
+}
+
+#[test]
+fn declared() {
+    let future: ParseDeclaredRetry<Attempts> = parse_retry("42".to_owned(), Attempts(2));
+    assert_eq!(block_on(future), Ok(42));
+    assert!(block_on(parse_retry("x".to_owned(), Attempts(2))).is_err());
+    assert_eq!(block_on(parse("7".to_owned())), Ok(7));
+}
+
+#[test]
+fn generic() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let future = Retry::<Flaky, _>::new((Arc::clone(&calls), 2), Attempts(2));
+    assert_eq!(block_on(future), Ok(2));
+}
+
+#[test]
+fn dropped() {
+    let calls = Arc::new(AtomicU32::new(0));
+    drop(flaky_retry(Arc::clone(&calls), 2, Attempts(2)));
+    assert_eq!(Arc::strong_count(&calls), 1);
+
+    let mut future = Box::pin(flaky_retry(Arc::clone(&calls), 2, Attempts(2)));
+    assert!(block_on(futures_lite::future::poll_once(future.as_mut())).is_none());
+    assert_eq!(Arc::strong_count(&calls), 3);
+    drop(future);
+    assert_eq!(Arc::strong_count(&calls), 1);
+}
+
+#[test]
+fn polled_after_completion() {
+    set_hook(Box::new(|_| ()));
+    let mut future = Box::pin(flaky_retry(Arc::new(AtomicU32::new(0)), 1, Attempts(1)));
+    assert_eq!(block_on(future.as_mut()), Ok(1));
+    let msg = catch_unwind(AssertUnwindSafe(|| block_on(future))).unwrap_err();
+    assert_eq!(
+        msg.downcast_ref::<&str>().unwrap(),
+        &"`Retry` was polled after it completed",
+    );
+}
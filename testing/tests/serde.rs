@@ -0,0 +1,144 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::future::Future;
+use std::pin::Pin;
+
+use futures_lite::future::{block_on, poll_once, yield_now};
+use named_future::{Lazy, NamedFuture, Storage, named_future, named_future_registry};
+use serde::{Deserialize, Serialize};
+
+#[named_future]
+async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+    yield_now().await;
+    factor1 * factor2
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Person {
+    name: String,
+    age: u8,
+}
+
+#[named_future(type = GreetFuture)]
+async fn greet(person: Person) -> String {
+    format!("Hello, {} ({})!", person.name, person.age)
+}
+
+#[derive(NamedFuture)]
+struct NothingDeclared(Storage<Self>);
+
+#[named_future(into = NothingDeclared)]
+async fn nothing() {}
+
+named_future_registry! {
+    /// A queued job
+    enum Job {
+        SlowMul(SlowMul),
+        /// Greet someone
+        Greet(GreetFuture),
+        Nothing(NothingDeclared),
+    }
+}
+
+#[test]
+fn names() {
+    assert_eq!(SlowMul::NAME, "SlowMul");
+    assert_eq!(GreetFuture::NAME, "GreetFuture");
+    assert_eq!(NothingDeclared::NAME, "NothingDeclared");
+    assert_eq!(block_on(nothing()), ());
+    assert_eq!(block_on(slow_mul(6, 7)), 42);
+    let person = Person {
+        name: "Ferris".to_owned(),
+        age: 8,
+    };
+    assert_eq!(block_on(greet(person)), "Hello, Ferris (8)!");
+}
+
+#[test]
+fn lazy_roundtrip() {
+    let future = Lazy::<SlowMul>::new((6, 7));
+    let json = serde_json::to_string(&future).unwrap();
+    assert_eq!(json, r#"{"name":"SlowMul","args":[6,7]}"#);
+
+    let future: Lazy<SlowMul> = serde_json::from_str(&json).unwrap();
+    assert_eq!(future.args(), Some(&(6, 7)));
+    assert_eq!(block_on(future), 42);
+
+    let future: Lazy<SlowMul> = serde_json::from_str(r#"["SlowMul",[2,3]]"#).unwrap();
+    assert_eq!(block_on(future), 6);
+}
+
+#[test]
+fn lazy_errors() {
+    let err = serde_json::from_str::<Lazy<SlowMul>>(r#"{"name":"GreetFuture","args":[1,2]}"#)
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("invalid value: string \"GreetFuture\", expected one of `SlowMul`"));
+
+    let err = serde_json::from_str::<Lazy<SlowMul>>(r#"{"args":[1,2],"name":"SlowMul"}"#)
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("`name` must precede `args`"));
+
+    let err = serde_json::from_str::<Lazy<SlowMul>>(r#"{"name":"SlowMul"}"#)
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("missing field `args`"));
+
+    let mut future = Box::pin(Lazy::<SlowMul>::new((6, 7)));
+    assert_eq!(block_on(poll_once(future.as_mut())), None);
+    let err = serde_json::to_string(&*future).unwrap_err().to_string();
+    assert_eq!(
+        err,
+        "`SlowMul` was started, so its arguments cannot be serialized"
+    );
+}
+
+#[test]
+fn registry() {
+    let jobs = vec![
+        Job::from(Lazy::<SlowMul>::new((6, 7))),
+        Job::from(Lazy::<GreetFuture>::new((Person {
+            name: "Ferris".to_owned(),
+            age: 8,
+        },))),
+        Job::from(Lazy::<NothingDeclared>::new(())),
+    ];
+    let json = serde_json::to_string(&jobs).unwrap();
+    assert_eq!(
+        json,
+        concat!(
+            r#"[{"name":"SlowMul","args":[6,7]},"#,
+            r#"{"name":"GreetFuture","args":[{"name":"Ferris","age":8}]},"#,
+            r#"{"name":"NothingDeclared","args":null}]"#,
+        ),
+    );
+
+    let jobs: Vec<Job> = serde_json::from_str(&json).unwrap();
+    let names: Vec<_> = jobs.iter().map(Job::name).collect();
+    assert_eq!(names, ["SlowMul", "GreetFuture", "NothingDeclared"]);
+    let outputs: Vec<String> = jobs
+        .into_iter()
+        .map(|job| -> Pin<Box<dyn Future<Output = String>>> {
+            match job {
+                Job::SlowMul(future) => Box::pin(async { future.await.to_string() }),
+                Job::Greet(future) => Box::pin(future),
+                Job::Nothing(future) => Box::pin(async { format!("{:?}", future.await) }),
+            }
+        })
+        .map(block_on)
+        .collect();
+    assert_eq!(outputs, ["42", "Hello, Ferris (8)!", "()"]);
+
+    let err = serde_json::from_str::<Job>(r#"{"name":"Unknown","args":[]}"#)
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with(concat!(
+        r#"invalid value: string "Unknown", "#,
+        "expected one of `SlowMul`, `GreetFuture`, `NothingDeclared`",
+    )));
+    assert_eq!(
+        format!("{:?}", Job::from(Lazy::<SlowMul>::new((1, 2)))),
+        "SlowMul(Lazy { started: false, .. })",
+    );
+}
@@ -0,0 +1,158 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::convert::Infallible;
+use std::future::poll_fn;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures_lite::future::block_on;
+use named_future::{NamedFuture, Storage, named_future};
+use tower_layer::Layer;
+use tower_service::Service;
+
+#[named_future(service)]
+async fn shout(req: String) -> Result<String, Infallible> {
+    Ok(req.to_uppercase())
+}
+
+#[named_future(service)]
+async fn count(counter: Arc<AtomicUsize>, req: usize) -> Result<usize, Infallible> {
+    Ok(counter.fetch_add(req, Ordering::Relaxed) + req)
+}
+
+#[named_future(service)]
+async fn parse(radix: u32, req: &'static str) -> Result<u32, std::num::ParseIntError> {
+    u32::from_str_radix(req, radix)
+}
+
+#[named_future(service, layer)]
+async fn exclaim(mut inner: ShoutService, req: String) -> Result<String, Infallible> {
+    poll_fn(|cx| inner.poll_ready(cx)).await?;
+    let resp = inner.call(req).await?;
+    Ok(format!("{resp}!"))
+}
+
+#[derive(Clone)]
+struct Greeter {
+    greeting: Arc<str>,
+}
+
+#[named_future(service, Send)]
+impl Greeter {
+    fn new(greeting: &str) -> Self {
+        Self {
+            greeting: greeting.into(),
+        }
+    }
+
+    /// Greet someone
+    async fn greet(&self, name: String) -> Result<String, Infallible> {
+        Ok(format!("{}, {name}!", self.greeting))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Offset(u32);
+
+#[named_future(service)]
+impl Offset {
+    async fn add(&self, other: Self) -> Result<Self, Infallible> {
+        Ok(Self(self.0 + other.0))
+    }
+}
+
+#[derive(NamedFuture)]
+struct DoubleDeclared(Storage<Self>);
+
+#[named_future(into = DoubleDeclared, service)]
+async fn double(req: u64) -> Result<u64, Infallible> {
+    Ok(req * 2)
+}
+
+fn oneshot<S: Service<R>, R>(mut service: S, req: R) -> Result<S::Response, S::Error> {
+    block_on(async move {
+        poll_fn(|cx| service.poll_ready(cx)).await?;
+        service.call(req).await
+    })
+}
+
+#[test]
+fn stateless() {
+    let mut service = ShoutService;
+    let future: Shout = service.call("hello".to_owned());
+    assert_eq!(block_on(future), Ok("HELLO".to_owned()));
+    assert_eq!(
+        oneshot(ShoutService, "world".to_owned()),
+        Ok("WORLD".to_owned())
+    );
+}
+
+#[test]
+fn stateful() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let mut service = CountService::new(Arc::clone(&counter));
+    let first = service.call(2);
+    let second = service.call(3);
+    assert_eq!(block_on(second), Ok(3));
+    assert_eq!(block_on(first), Ok(5));
+    assert_eq!(counter.load(Ordering::Relaxed), 5);
+}
+
+#[test]
+fn error() {
+    assert_eq!(oneshot(ParseService::new(16), "2a"), Ok(42));
+    assert!(oneshot(ParseService::new(10), "2a").is_err());
+}
+
+#[test]
+fn layer() {
+    let service: ExclaimService = ExclaimLayer.layer(ShoutService);
+    assert_eq!(oneshot(service, "hi".to_owned()), Ok("HI!".to_owned()));
+}
+
+#[test]
+fn method() {
+    let greeter = Greeter::new("Hello");
+    assert_eq!(
+        block_on(greeter.greet("method".to_owned())),
+        Ok("Hello, method!".to_owned()),
+    );
+
+    let mut service = GreetService::new(greeter.clone());
+    let future: Greet = service.call("service".to_owned());
+    assert_eq!(block_on(future), Ok("Hello, service!".to_owned()));
+    assert_eq!(
+        block_on(greet(greeter, "fn".to_owned())),
+        Ok("Hello, fn!".to_owned())
+    );
+
+    let sum = oneshot(AddService::new(Offset(40)), Offset(2)).unwrap();
+    assert_eq!(sum.0, 42);
+}
+
+#[test]
+fn declared() {
+    let future: DoubleDeclared = DoubleDeclaredService.call(21);
+    assert_eq!(block_on(future), Ok(42));
+    assert_eq!(block_on(double(4)), Ok(8));
+}
+
+#[test]
+fn direct_call() {
+    assert_eq!(block_on(shout("x".to_owned())), Ok("X".to_owned()));
+    let counter = Arc::new(AtomicUsize::new(1));
+    assert_eq!(block_on(count(counter, 1)), Ok(2));
+    assert_eq!(block_on(parse(2, "101")), Ok(5));
+    let resp = block_on(exclaim(ShoutService, "a".to_owned()));
+    assert_eq!(resp, Ok("A!".to_owned()));
+}
+
+#[test]
+fn debug() {
+    assert_eq!(
+        format!("{:?}", CountService::new(Arc::default())),
+        "CountService { .. }"
+    );
+    assert_eq!(format!("{ShoutService:?}"), "ShoutService");
+    assert_eq!(format!("{ExclaimLayer:?}"), "ExclaimLayer");
+}
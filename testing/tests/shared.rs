@@ -0,0 +1,180 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::future::Future;
+use std::panic::{AssertUnwindSafe, catch_unwind, set_hook};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread;
+
+use futures_lite::future::{block_on, poll_once, yield_now};
+use named_future::{NamedFuture, Storage, named_future};
+
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[named_future(Send, Sync, shared)]
+async fn slow_mul(factor1: u32, factor2: u32) -> u32 {
+    CALLS.fetch_add(1, Ordering::SeqCst);
+    yield_now().await;
+    factor1 * factor2
+}
+
+#[named_future(shared)]
+async fn first<'a>(values: &'a [String]) -> Option<&'a String> {
+    yield_now().await;
+    values.first()
+}
+
+#[named_future(shared)]
+async fn fails() -> u32 {
+    yield_now().await;
+    panic!("Oh no!");
+}
+
+#[derive(NamedFuture)]
+struct Declared(Storage<Self>);
+
+#[named_future(into = Declared, shared)]
+async fn declared() -> &'static str {
+    "declared"
+}
+
+#[named_future(shared)]
+async fn wait_for(gate: Arc<Gate>) -> u32 {
+    GateFuture(&gate).await;
+    42
+}
+
+#[derive(Default)]
+struct Gate {
+    open: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Gate {
+    fn open(&self) {
+        self.open.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+struct GateFuture<'a>(&'a Gate);
+
+impl Future for GateFuture<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        *self.0.waker.lock().unwrap() = Some(cx.waker().clone());
+        if self.0.open.load(Ordering::SeqCst) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn polled_once() {
+    let before = CALLS.load(Ordering::SeqCst);
+    let future = slow_mul_shared(6, 7);
+    let clone = future.clone();
+    assert_eq!(future.peek(), None);
+    assert_eq!(block_on(future), 42);
+    assert_eq!(clone.peek(), Some(&42));
+    assert_eq!(block_on(clone.clone()), 42);
+    assert_eq!(block_on(clone), 42);
+    assert_eq!(CALLS.load(Ordering::SeqCst), before + 1);
+    assert_eq!(block_on(slow_mul(6, 7)), 42);
+}
+
+#[test]
+fn waiters_are_woken() {
+    let gate = Arc::new(Gate::default());
+    let mut first = wait_for_shared(Arc::clone(&gate));
+    let mut second = first.clone();
+
+    let first_counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let first_waker = Waker::from(Arc::clone(&first_counter));
+    let second_counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let second_waker = Waker::from(Arc::clone(&second_counter));
+
+    let poll =
+        |future: &mut WaitForShared, waker| Pin::new(future).poll(&mut Context::from_waker(waker));
+    assert_eq!(poll(&mut first, &first_waker), Poll::Pending);
+    assert_eq!(poll(&mut second, &second_waker), Poll::Pending);
+    assert_eq!(first_counter.0.load(Ordering::SeqCst), 0);
+    assert_eq!(second_counter.0.load(Ordering::SeqCst), 0);
+
+    gate.open();
+    assert_eq!(first_counter.0.load(Ordering::SeqCst), 1);
+    assert_eq!(second_counter.0.load(Ordering::SeqCst), 1);
+    assert_eq!(poll(&mut second, &second_waker), Poll::Ready(42));
+    assert_eq!(poll(&mut first, &first_waker), Poll::Ready(42));
+    assert_eq!(block_on(wait_for(gate)), 42);
+}
+
+#[test]
+fn dropped_poller() {
+    let mut first = Box::pin(slow_mul_shared(6, 7));
+    let second = first.clone();
+    assert!(block_on(poll_once(first.as_mut())).is_none());
+    drop(first);
+    assert_eq!(block_on(second), 42);
+}
+
+#[test]
+fn threads() {
+    let future = slow_mul_shared(6, 7);
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let future = future.clone();
+            thread::spawn(move || block_on(future))
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), 42);
+    }
+    assert_eq!(future.peek(), Some(&42));
+}
+
+#[test]
+fn references() {
+    let values = vec!["hello".to_owned(), "world".to_owned()];
+    let future: FirstShared<'_> = first_shared(&values);
+    assert_eq!(block_on(future.clone()), Some(&values[0]));
+    assert_eq!(block_on(future), Some(&values[0]));
+    assert_eq!(block_on(first(&values)), Some(&values[0]));
+}
+
+#[test]
+fn declared_struct() {
+    let future: DeclaredShared = declared_shared();
+    assert_eq!(block_on(future.clone()), "declared");
+    assert_eq!(block_on(future), "declared");
+    assert_eq!(block_on(self::declared()), "declared");
+}
+
+#[test]
+fn panic() {
+    set_hook(Box::new(|_| ()));
+    let future = fails_shared();
+    let clone = future.clone();
+    let _ = catch_unwind(AssertUnwindSafe(|| block_on(future))).unwrap_err();
+    let msg = catch_unwind(AssertUnwindSafe(|| block_on(clone))).unwrap_err();
+    assert_eq!(
+        msg.downcast_ref::<String>().unwrap(),
+        "`Fails` was polled after it panicked",
+    );
+    drop(fails());
+}
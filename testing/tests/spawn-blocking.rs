@@ -0,0 +1,176 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::future::Future;
+use std::io;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Wake, Waker};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+use futures_lite::future::{block_on, poll_once};
+use named_future::{BlockingExecutor, BlockingTask, ThreadPool, named_future};
+
+/// Pretend to hash a file
+#[named_future(spawn_blocking, Send)]
+fn hash_file(path: PathBuf) -> io::Result<(u64, ThreadId)> {
+    if path.as_os_str().is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "empty path"));
+    }
+    let hash = path.as_os_str().len() as u64;
+    Ok((hash, thread::current().id()))
+}
+
+static POOL: ThreadPool = ThreadPool::new(2, Duration::from_millis(100));
+
+#[named_future(spawn_blocking = POOL)]
+fn thread_name() -> Option<String> {
+    thread::current().name().map(str::to_owned)
+}
+
+#[named_future(spawn_blocking, catch_unwind)]
+fn checked_div(dividend: u32, divisor: u32) -> u32 {
+    dividend / divisor
+}
+
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Queues the tasks until `run_all()` is called
+struct Manual;
+
+static QUEUE: Mutex<Vec<BlockingTask>> = Mutex::new(Vec::new());
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+impl Manual {
+    fn run_all() -> usize {
+        let tasks = std::mem::take(&mut *QUEUE.lock().unwrap());
+        let count = tasks.len();
+        for task in tasks {
+            task();
+        }
+        count
+    }
+}
+
+impl BlockingExecutor for Manual {
+    fn spawn_blocking(&'static self, task: BlockingTask) {
+        QUEUE.lock().unwrap().push(task);
+    }
+}
+
+#[named_future(spawn_blocking = Manual)]
+fn count_run(value: u32) -> u32 {
+    RUNS.fetch_add(1, Ordering::SeqCst);
+    value
+}
+
+/// Drops every task without running it
+struct Dropping;
+
+impl BlockingExecutor for Dropping {
+    fn spawn_blocking(&'static self, task: BlockingTask) {
+        drop(task);
+    }
+}
+
+#[named_future(spawn_blocking = Dropping)]
+fn never_run() -> u32 {
+    unreachable!()
+}
+
+/// Keeps the tasks, until they are dropped without running them
+struct Deferred;
+
+static DEFERRED: Mutex<Vec<BlockingTask>> = Mutex::new(Vec::new());
+
+impl BlockingExecutor for Deferred {
+    fn spawn_blocking(&'static self, task: BlockingTask) {
+        DEFERRED.lock().unwrap().push(task);
+    }
+}
+
+#[named_future(spawn_blocking = Deferred)]
+fn run_later() -> u32 {
+    unreachable!()
+}
+
+fn assert_send<T: Send>(value: T) -> T {
+    value
+}
+
+#[cfg_attr(miri, ignore)] // leaves pool threads running
+#[test]
+fn global_pool() {
+    let future: HashFile = assert_send(hash_file(PathBuf::from("hello")));
+    let (hash, thread_id) = block_on(future).unwrap();
+    assert_eq!(hash, 5);
+    assert_ne!(thread_id, thread::current().id());
+
+    let err = block_on(hash_file(PathBuf::new())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+}
+
+#[cfg_attr(miri, ignore)] // leaves pool threads running
+#[test]
+fn static_pool() {
+    let futures = (0..8).map(|_| thread_name()).collect::<Vec<_>>();
+    for future in futures {
+        assert_eq!(block_on(future).as_deref(), Some("named-future-blocking"));
+    }
+    assert!(format!("{POOL:?}").starts_with("ThreadPool { max_threads: 2, "));
+}
+
+#[cfg_attr(miri, ignore)] // leaves pool threads running
+#[test]
+fn panics() {
+    std::panic::set_hook(Box::new(|_| ()));
+    assert_eq!(block_on(checked_div(6, 3)).unwrap(), 2);
+    assert!(block_on(checked_div(6, 0)).is_err());
+}
+
+#[test]
+fn cancellation() {
+    // Dropped before the job started: the job is skipped.
+    let mut future = Box::pin(count_run(1));
+    assert!(block_on(poll_once(future.as_mut())).is_none());
+    drop(future);
+    assert_eq!(Manual::run_all(), 1);
+    assert_eq!(RUNS.load(Ordering::SeqCst), 0);
+
+    // Not polled: the job was not even spawned.
+    drop(count_run(2));
+    assert_eq!(Manual::run_all(), 0);
+
+    let mut future = Box::pin(count_run(3));
+    assert!(block_on(poll_once(future.as_mut())).is_none());
+    assert_eq!(Manual::run_all(), 1);
+    assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+    assert_eq!(block_on(future.as_mut()), 3);
+}
+
+#[test]
+fn dropped_by_executor() {
+    std::panic::set_hook(Box::new(|_| ()));
+    let msg = "the executor dropped the blocking job without running it";
+
+    let err = catch_unwind(|| block_on(never_run())).unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&msg));
+
+    let counter = Arc::new(Counter(AtomicUsize::new(0)));
+    let waker = Waker::from(Arc::clone(&counter));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(run_later());
+    assert!(future.as_mut().poll(&mut cx).is_pending());
+    DEFERRED.lock().unwrap().clear();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    let err = catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(&mut cx))).unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&msg));
+}
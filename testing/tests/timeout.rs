@@ -0,0 +1,114 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use std::future::{pending, ready};
+use std::time::{Duration, Instant};
+
+use futures_lite::future::{block_on, yield_now};
+use named_future::{
+    AsyncIoTimer, Elapsed, NamedFuture, StdTimer, Storage, Timeout, Timer, TokioTimer, named_future,
+};
+
+#[named_future(timeout)]
+async fn slow_mul(factor1: u32, factor2: u32, timer: StdTimer, delay: Duration) -> u32 {
+    timer.sleep(delay).await;
+    factor1 * factor2
+}
+
+#[named_future(timeout)]
+async fn yield_first<'a>(text: &'a str) -> &'a str {
+    yield_now().await;
+    text
+}
+
+#[derive(NamedFuture)]
+struct CountDeclared(Storage<Self>);
+
+#[named_future(into = CountDeclared, timeout)]
+async fn count(yields: usize) -> usize {
+    for _ in 0..yields {
+        yield_now().await;
+    }
+    yields
+}
+
+#[cfg_attr(miri, ignore)] // sleeps on detached threads
+#[test]
+fn std_timer() {
+    let timer = StdTimer;
+    let future: SlowMulTimeout<_> = slow_mul_with_deadline(
+        6,
+        7,
+        timer,
+        Duration::from_millis(1),
+        timer.sleep(Duration::from_secs(60)),
+    );
+    assert_eq!(block_on(future), Ok(42));
+
+    let start = Instant::now();
+    let future = slow_mul_with_deadline(
+        6,
+        7,
+        timer,
+        Duration::from_secs(60),
+        timer.sleep(Duration::from_millis(10)),
+    );
+    assert_eq!(block_on(future), Err(Elapsed));
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert!(start.elapsed() < Duration::from_secs(60));
+
+    assert_eq!(block_on(slow_mul(6, 7, timer, Duration::ZERO)), 42);
+}
+
+#[test]
+fn deadlines() {
+    assert_eq!(block_on(yield_first_with_deadline("x", pending())), Ok("x"));
+    assert_eq!(
+        block_on(yield_first_with_deadline("x", ready(()))),
+        Err(Elapsed)
+    );
+    assert_eq!(block_on(yield_first("x")), "x");
+
+    let future: CountDeclaredTimeout<_> = count_with_deadline(3, pending());
+    assert_eq!(block_on(future), Ok(3));
+    assert_eq!(block_on(count_with_deadline(0, ready(()))), Ok(0));
+    assert_eq!(block_on(count(1)), 1);
+
+    let future = Timeout::new(CountDeclared::new((2,)), pending());
+    assert_eq!(block_on(future), Ok(2));
+}
+
+#[cfg_attr(miri, ignore)] // uses OS timers
+#[test]
+fn tokio_timer() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let future = count_with_deadline(3, TokioTimer.sleep(Duration::from_secs(60)));
+        assert_eq!(future.await, Ok(3));
+        let future = slow_mul_with_deadline(
+            6,
+            7,
+            StdTimer,
+            Duration::from_secs(60),
+            TokioTimer.sleep(Duration::from_millis(10)),
+        );
+        assert_eq!(future.await, Err(Elapsed));
+    });
+}
+
+#[cfg_attr(miri, ignore)] // uses OS timers
+#[test]
+fn async_io_timer() {
+    let future = count_with_deadline(3, AsyncIoTimer.sleep(Duration::from_secs(60)));
+    assert_eq!(block_on(future), Ok(3));
+    let future = slow_mul_with_deadline(
+        6,
+        7,
+        StdTimer,
+        Duration::from_secs(60),
+        AsyncIoTimer.sleep(Duration::from_millis(10)),
+    );
+    assert_eq!(block_on(future), Err(Elapsed));
+}
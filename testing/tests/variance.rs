@@ -0,0 +1,37 @@
+#![cfg_attr(feature = "nightly", feature(type_alias_impl_trait))]
+
+use futures_lite::future::block_on;
+use named_future::{named_future, named_future_fn};
+
+#[named_future]
+async fn longest<'a, 'b>(left: &'a str, right: &'b str) -> usize {
+    left.len().max(right.len())
+}
+
+named_future_fn! {
+    async fn first<'a>(values: &'a [u32]) -> Option<&'a u32> as First {
+        values.first()
+    }
+}
+
+// Arguments that are only read can still be shortened.
+// Opaque types are always invariant, so this does not work with the `nightly` backend.
+#[cfg(not(feature = "nightly"))]
+fn shrink_longest<'short>(future: Longest<'static, 'static>) -> Longest<'short, 'short> {
+    future
+}
+
+fn shrink_first<'short>(future: First<'static>) -> First<'short> {
+    future
+}
+
+#[test]
+fn covariant() {
+    let owned = String::from("hello");
+    #[cfg(not(feature = "nightly"))]
+    assert_eq!(block_on(shrink_longest(longest("a", "bc"))), 2);
+    assert_eq!(block_on(longest(&owned, "bc")), 5);
+
+    let future = shrink_first(first(&[1, 2, 3]));
+    assert_eq!(block_on(future), Some(&1));
+}